@@ -0,0 +1,466 @@
+//! Replays join and leave streams through the matchmaking rules and reports how long people wait,
+//! so rule changes can be compared before they reach a board.
+//!
+//! Recorded streams have one event per line, in time order. Queues are `a`, `b` and `c`, and the
+//! World Level is optional:
+//!
+//! ```text
+//! # seconds  event  user  queue  world level
+//! 0          join   1     a
+//! 12.5       join   2     b      6
+//! 40         leave  1
+//! ```
+//!
+//! ```text
+//! simulate [--coop-size N] [--timeout SECONDS] FILE
+//! simulate [--coop-size N] [--timeout SECONDS] --synthetic [--hours H] [--rate-a PER_HOUR]
+//!          [--rate-b PER_HOUR] [--rate-c PER_HOUR] [--patience SECONDS] [--seed N]
+//! ```
+
+use std::collections::HashMap;
+use std::{env, fs, process};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use ww_bot::matchmaking::{self, Entry, Event, Queues, Role};
+
+const ROLES: [Role; 3] = [Role::Coop, Role::Carry, Role::Assist];
+
+struct Options {
+    coop_size: usize,
+    /// Users are removed once they have waited this long.
+    timeout: Option<f64>,
+    source: Source,
+}
+
+enum Source {
+    File(String),
+    Synthetic(Synthetic),
+}
+
+struct Synthetic {
+    hours: f64,
+    /// Joins per hour for queues 1, 2 and 3.
+    rates: [f64; 3],
+    /// How long users wait on average before giving up.
+    patience: f64,
+    seed: u64,
+}
+
+struct Timed {
+    at: f64,
+    event: Event<u64>,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: simulate [--coop-size N] [--timeout SECONDS] FILE");
+    eprintln!("       simulate [--coop-size N] [--timeout SECONDS] --synthetic [--hours H] [--rate-a PER_HOUR] [--rate-b PER_HOUR] [--rate-c PER_HOUR] [--patience SECONDS] [--seed N]");
+    process::exit(2)
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut coop_size = 3;
+    let mut timeout = None;
+    let mut file = None;
+    let mut synthetic = false;
+    let mut generated = Synthetic {
+        hours: 24.0,
+        rates: [30.0, 12.0, 8.0],
+        patience: 1800.0,
+        seed: 0,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+
+        match arg.as_str() {
+            "--coop-size" => coop_size = number(&value(&arg)?)?,
+            "--timeout" => timeout = Some(number(&value(&arg)?)?),
+            "--synthetic" => synthetic = true,
+            "--hours" => generated.hours = number(&value(&arg)?)?,
+            "--rate-a" => generated.rates[0] = number(&value(&arg)?)?,
+            "--rate-b" => generated.rates[1] = number(&value(&arg)?)?,
+            "--rate-c" => generated.rates[2] = number(&value(&arg)?)?,
+            "--patience" => generated.patience = number(&value(&arg)?)?,
+            "--seed" => generated.seed = number(&value(&arg)?)?,
+            "--help" | "-h" => usage(),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => file = Some(arg),
+        }
+    }
+
+    if coop_size < 2 {
+        return Err("--coop-size must be at least 2".to_owned());
+    }
+
+    let source = match (synthetic, file) {
+        (true, None) => Source::Synthetic(generated),
+        (false, Some(file)) => Source::File(file),
+        _ => return Err("give either a file or --synthetic".to_owned()),
+    };
+
+    Ok(Options { coop_size, timeout, source })
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} is not a valid number", value))
+}
+
+fn parse_role(value: &str) -> Result<Role, String> {
+    match value {
+        "a" | "1" | "coop" => Ok(Role::Coop),
+        "b" | "2" | "carry" => Ok(Role::Carry),
+        "c" | "3" | "assist" => Ok(Role::Assist),
+        _ => Err(format!("{} is not a queue", value)),
+    }
+}
+
+fn parse_line(line: &str) -> Result<Option<Timed>, String> {
+    let line = line.split('#').next().unwrap_or_default();
+    let fields = line.split_whitespace().collect::<Vec<&str>>();
+
+    let event = match fields[..] {
+        [] => return Ok(None),
+        [_, "join", user, queue] => Event::Join(parse_role(queue)?, Entry::new(number(user)?, None)),
+        [_, "join", user, queue, level] => Event::Join(parse_role(queue)?, Entry::new(number(user)?, Some(number(level)?))),
+        [_, "leave", user] => Event::Leave(number(user)?),
+        _ => return Err(format!("could not read `{}`", line.trim())),
+    };
+
+    Ok(Some(Timed { at: number(fields[0])?, event }))
+}
+
+fn parse_stream(text: &str) -> Result<Vec<Timed>, String> {
+    let mut events = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        if let Some(event) = parse_line(line).map_err(|i| format!("line {}: {}", index + 1, i))? {
+            if events.last().is_some_and(|i: &Timed| i.at > event.at) {
+                return Err(format!("line {}: events must be in time order", index + 1));
+            }
+            events.push(event);
+        }
+    }
+
+    Ok(events)
+}
+
+/// A sample from an exponential distribution, for arrivals and patience.
+fn exponential(rng: &mut StdRng, mean: f64) -> f64 {
+    -mean * (1.0 - rng.gen::<f64>()).ln()
+}
+
+/// Poisson arrivals on each queue, each user leaving after their patience runs out unless grouped first.
+fn generate(options: &Synthetic) -> Vec<Timed> {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut events = Vec::new();
+    let mut user = 0;
+    let end = options.hours * 3600.0;
+
+    for (role, rate) in ROLES.into_iter().zip(options.rates) {
+        if rate <= 0.0 {
+            continue;
+        }
+
+        let mut at = exponential(&mut rng, 3600.0 / rate);
+        while at < end {
+            user += 1;
+            let level = rng.gen_range(1..=8);
+
+            events.push(Timed { at, event: Event::Join(role, Entry::new(user, Some(level))) });
+            events.push(Timed { at: at + exponential(&mut rng, options.patience), event: Event::Leave(user) });
+
+            at += exponential(&mut rng, 3600.0 / rate);
+        }
+    }
+
+    events.sort_by(|a, b| a.at.total_cmp(&b.at));
+    events
+}
+
+fn index(role: Role) -> usize {
+    match role {
+        Role::Coop => 0,
+        Role::Carry => 1,
+        Role::Assist => 2,
+    }
+}
+
+#[derive(Default)]
+struct Report {
+    joined: [usize; 3],
+    left: [usize; 3],
+    timed_out: [usize; 3],
+    /// Seconds waited by everyone who was grouped.
+    waits: [Vec<f64>; 3],
+    /// Seconds waited so far by everyone still in a queue at the end.
+    waiting: [Vec<f64>; 3],
+    coop_groups: usize,
+    carry_groups: usize,
+    /// Queue length integrated over time, for averages.
+    length_time: [f64; 3],
+    duration: f64,
+}
+
+struct Simulation {
+    queues: Queues<u64>,
+    coop_size: usize,
+    timeout: Option<f64>,
+    joined: HashMap<u64, (Role, f64)>,
+    now: f64,
+    report: Report,
+}
+
+impl Simulation {
+    fn new(coop_size: usize, timeout: Option<f64>) -> Self {
+        Self {
+            queues: Queues::default(),
+            coop_size,
+            timeout,
+            joined: HashMap::new(),
+            now: 0.0,
+            report: Report::default(),
+        }
+    }
+
+    fn advance(&mut self, at: f64) {
+        let elapsed = (at - self.now).max(0.0);
+        for role in ROLES {
+            self.report.length_time[index(role)] += elapsed * self.queues.queue(role).len() as f64;
+        }
+        self.now = self.now.max(at);
+    }
+
+    /// Removes everyone whose wait ran past the timeout before `at`, at the moment it ran out.
+    fn expire(&mut self, at: f64) {
+        let Some(timeout) = self.timeout else {
+            return;
+        };
+
+        let mut expired = self.joined.iter()
+            .map(|(user, (role, joined))| (joined + timeout, *user, *role))
+            .filter(|(expiry, _, _)| *expiry <= at)
+            .collect::<Vec<(f64, u64, Role)>>();
+        expired.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        for (expiry, user, role) in expired {
+            self.advance(expiry);
+            self.step(Event::Leave(user));
+            self.report.timed_out[index(role)] += 1;
+        }
+    }
+
+    fn step(&mut self, event: Event<u64>) {
+        match &event {
+            Event::Join(role, entry) if !self.queues.contains(&entry.user) => {
+                self.report.joined[index(*role)] += 1;
+                self.joined.insert(entry.user, (*role, self.now));
+            },
+            Event::Leave(user) => {
+                self.joined.remove(user);
+            },
+            _ => (),
+        }
+
+        let queues = std::mem::take(&mut self.queues);
+        let (queues, groups) = matchmaking::apply(queues, self.coop_size, event);
+        self.queues = queues;
+
+        for group in groups {
+            match group.queue_type {
+                false => self.report.coop_groups += 1,
+                true => self.report.carry_groups += 1,
+            }
+
+            for member in group.members {
+                if let Some((role, joined)) = self.joined.remove(&member.user) {
+                    self.report.waits[index(role)].push(self.now - joined);
+                }
+            }
+        }
+    }
+
+    fn run(mut self, events: Vec<Timed>) -> Report {
+        for timed in events {
+            self.expire(timed.at);
+            self.advance(timed.at);
+
+            if let Event::Leave(user) = &timed.event {
+                if let Some((role, _)) = self.joined.get(user) {
+                    self.report.left[index(*role)] += 1;
+                }
+            }
+
+            self.step(timed.event);
+        }
+
+        for (role, joined) in self.joined.values() {
+            self.report.waiting[index(*role)].push(self.now - joined);
+        }
+        self.report.duration = self.now;
+
+        self.report
+    }
+}
+
+/// The nearest-rank percentile of sorted values.
+fn percentile(sorted: &[f64], percent: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = ((percent / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+fn format_duration(seconds: Option<f64>) -> String {
+    let Some(seconds) = seconds else {
+        return "-".to_owned();
+    };
+
+    let seconds = seconds.round() as u64;
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+impl Report {
+    fn print(&mut self) {
+        let hours = self.duration / 3600.0;
+        let per_hour = |count: usize| if hours > 0.0 { count as f64 / hours } else { 0.0 };
+
+        println!("Simulated {:.1} hours, {} joins", hours, self.joined.iter().sum::<usize>());
+        println!("Groups formed: {} co-op ({:.1}/h), {} carry ({:.1}/h)",
+                 self.coop_groups, per_hour(self.coop_groups),
+                 self.carry_groups, per_hour(self.carry_groups));
+        println!();
+        println!("{:<16}{:>8}{:>9}{:>7}{:>11}{:>9}{:>10}{:>10}{:>10}{:>10}{:>15}",
+                 "Queue", "joined", "grouped", "left", "timed out", "waiting", "p50", "p90", "p99", "max", "longest left");
+
+        for (role, name) in ROLES.into_iter().zip(["Queue 1 Co-op", "Queue 2 Carry", "Queue 3 Assist"]) {
+            let i = index(role);
+            self.waits[i].sort_by(f64::total_cmp);
+            self.waiting[i].sort_by(f64::total_cmp);
+            let waits = &self.waits[i];
+
+            println!("{:<16}{:>8}{:>9}{:>7}{:>11}{:>9}{:>10}{:>10}{:>10}{:>10}{:>15}",
+                     name,
+                     self.joined[i],
+                     waits.len(),
+                     self.left[i],
+                     self.timed_out[i],
+                     self.waiting[i].len(),
+                     format_duration(percentile(waits, 50.0)),
+                     format_duration(percentile(waits, 90.0)),
+                     format_duration(percentile(waits, 99.0)),
+                     format_duration(waits.last().copied()),
+                     format_duration(self.waiting[i].last().copied()));
+        }
+
+        // Starved users gave up or are still waiting, rather than being grouped.
+        println!();
+        for (role, name) in ROLES.into_iter().zip(["Queue 1", "Queue 2", "Queue 3"]) {
+            let i = index(role);
+            let starved = self.left[i] + self.timed_out[i] + self.waiting[i].len();
+            let share = if self.joined[i] > 0 { 100.0 * starved as f64 / self.joined[i] as f64 } else { 0.0 };
+            println!("{} starvation: {} of {} users ({:.1}%) were never grouped", name, starved, self.joined[i], share);
+        }
+
+        let average = |i: usize| if self.duration > 0.0 { self.length_time[i] / self.duration } else { 0.0 };
+        let (carries, assists) = (average(1), average(2));
+        let joins = if self.joined[2] > 0 { self.joined[1] as f64 / self.joined[2] as f64 } else { 0.0 };
+
+        println!();
+        println!("Queue 2/3 balance: {:.2} carries and {:.2} assists waiting on average", carries, assists);
+        println!("{:.2} carries joined per assist, where each carry group needs 2", joins);
+    }
+}
+
+fn main() {
+    let options = parse_options(env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        usage()
+    });
+
+    let events = match &options.source {
+        Source::File(path) => {
+            let text = fs::read_to_string(path).unwrap_or_else(|error| {
+                eprintln!("could not read {}: {}", path, error);
+                process::exit(1)
+            });
+
+            parse_stream(&text).unwrap_or_else(|error| {
+                eprintln!("{}: {}", path, error);
+                process::exit(1)
+            })
+        },
+        Source::Synthetic(synthetic) => generate(synthetic),
+    };
+
+    Simulation::new(options.coop_size, options.timeout)
+        .run(events)
+        .print();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, parse_stream, percentile, Simulation, Synthetic};
+
+    #[test]
+    fn replays_a_recorded_stream() {
+        let events = parse_stream("
+            # a co-op group forms at 30s, and the carry gives up
+            0    join  1 a
+            10   join  2 a
+            20   join  4 b 6
+            30   join  3 a
+            50   leave 4
+        ").expect("stream is valid");
+
+        let report = Simulation::new(3, None).run(events);
+
+        assert_eq!(report.coop_groups, 1);
+        assert_eq!(report.waits[0], vec![30.0, 20.0, 0.0]);
+        assert_eq!(report.left[1], 1);
+        assert!(report.waiting.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn timeouts_remove_users_when_they_run_out() {
+        let events = parse_stream("0 join 1 c 5\n100 join 2 c 5\n").expect("stream is valid");
+
+        let report = Simulation::new(3, Some(60.0)).run(events);
+
+        assert_eq!(report.timed_out[2], 1);
+        assert_eq!(report.waiting[2], vec![0.0]);
+    }
+
+    #[test]
+    fn rejects_out_of_order_streams() {
+        assert!(parse_stream("10 join 1 a\n5 join 2 a\n").is_err());
+        assert!(parse_stream("0 join 1 d\n").is_err());
+    }
+
+    #[test]
+    fn synthetic_streams_are_reproducible() {
+        let options = Synthetic { hours: 2.0, rates: [20.0, 10.0, 5.0], patience: 600.0, seed: 7 };
+
+        let first = Simulation::new(3, None).run(generate(&options));
+        let second = Simulation::new(3, None).run(generate(&options));
+
+        assert_eq!(first.joined, second.joined);
+        assert_eq!(first.waits, second.waits);
+        assert!(first.coop_groups > 0);
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let values = [1.0, 2.0, 3.0, 4.0];
+
+        assert_eq!(percentile(&values, 50.0), Some(2.0));
+        assert_eq!(percentile(&values, 99.0), Some(4.0));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use twilight_model::id::{
+    Id,
+    marker::{
+        ChannelMarker,
+        MessageMarker,
+    }
+};
+
+use crate::matchmaking::Role;
+
+/// Bumped whenever the encoding of an existing variant changes, so old components are rejected.
+const VERSION: &str = "v1";
+
+/// How many bytes of the HMAC are kept. Custom ids are limited to 100 characters.
+const SIGNATURE_LEN: usize = 8;
+
+/// Every component the bot sends, and what it needs to handle a click.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CustomId {
+    Queue(Role),
+    Cancel(Id<MessageMarker>),
+    End(String),
+    Replace,
+    Requeue(Id<ChannelMarker>),
+    Together(Id<ChannelMarker>),
+    Targets(Id<MessageMarker>),
+    Report(String),
+    ReportModal(String),
+    ReportReason,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    Malformed,
+    Stale,
+    Signature,
+}
+
+impl DecodeError {
+    /// What to tell the user who clicked the component.
+    pub fn description(&self) -> &'static str {
+        match self {
+            DecodeError::Malformed => "This component was not recognised.",
+            DecodeError::Stale => "This component is out of date. Please use a newer message or command.",
+            DecodeError::Signature => "This component could not be verified.",
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Ids are signed when `CUSTOM_ID_SECRET` is set, and unsigned ids are then refused.
+fn secret() -> Option<String> {
+    std::env::var("CUSTOM_ID_SECRET").ok().filter(|i| !i.is_empty())
+}
+
+fn mac(secret: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn parse_id<T>(value: Option<&str>) -> Result<Id<T>, DecodeError> {
+    value.and_then(|i| i.parse::<u64>().ok())
+        .and_then(Id::new_checked)
+        .ok_or(DecodeError::Malformed)
+}
+
+fn parse_token(value: Option<&str>) -> Result<String, DecodeError> {
+    value.filter(|i| !i.is_empty() && i.chars().all(|i| i.is_ascii_alphanumeric()))
+        .map(|i| i.to_owned())
+        .ok_or(DecodeError::Malformed)
+}
+
+impl CustomId {
+    /// The first field of the encoded id, which components are registered under.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            CustomId::Queue(_) => "queue",
+            CustomId::Cancel(_) => "cancel",
+            CustomId::End(_) => "end",
+            CustomId::Replace => "replace",
+            CustomId::Requeue(_) => "requeue",
+            CustomId::Together(_) => "together",
+            CustomId::Targets(_) => "targets",
+            CustomId::Report(_) => "draft",
+            CustomId::ReportModal(_) => "draftmodal",
+            CustomId::ReportReason => "reportreason",
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let mut fields = vec![self.tag().to_owned()];
+
+        match self {
+            CustomId::Queue(role) => fields.push(role.value().to_owned()),
+            CustomId::Cancel(board) | CustomId::Targets(board) => fields.push(board.to_string()),
+            CustomId::Requeue(thread) | CustomId::Together(thread) => fields.push(thread.to_string()),
+            CustomId::End(token) | CustomId::Report(token) | CustomId::ReportModal(token) => fields.push(token.clone()),
+            CustomId::Replace | CustomId::ReportReason => (),
+        }
+
+        let payload = format!("{}:{}", VERSION, fields.join(":"));
+
+        match secret() {
+            Some(secret) => {
+                let signature = mac(&secret, &payload).finalize().into_bytes();
+                let signature = signature[..SIGNATURE_LEN].iter()
+                    .map(|i| format!("{:02x}", i))
+                    .collect::<String>();
+
+                format!("{}:{}", payload, signature)
+            },
+            None => payload,
+        }
+    }
+
+    pub fn decode(custom_id: &str) -> Result<Self, DecodeError> {
+        // Queue boards are long lived, so their buttons from before versioning keep working.
+        if let Some(role) = Self::decode_board(custom_id) {
+            return Ok(CustomId::Queue(role));
+        }
+
+        let Some(payload) = custom_id.strip_prefix(VERSION).and_then(|i| i.strip_prefix(':')) else {
+            return Err(DecodeError::Stale);
+        };
+
+        let payload = match secret() {
+            Some(secret) => {
+                let (payload, signature) = payload.rsplit_once(':').ok_or(DecodeError::Signature)?;
+
+                let signature = (0..signature.len())
+                    .step_by(2)
+                    .map(|i| signature.get(i..i + 2).and_then(|i| u8::from_str_radix(i, 16).ok()))
+                    .collect::<Option<Vec<u8>>>()
+                    .filter(|i| i.len() == SIGNATURE_LEN)
+                    .ok_or(DecodeError::Signature)?;
+
+                mac(&secret, &format!("{}:{}", VERSION, payload))
+                    .verify_truncated_left(&signature)
+                    .map_err(|_| DecodeError::Signature)?;
+
+                payload
+            },
+            None => payload,
+        };
+
+        let mut fields = payload.split(':');
+        let tag = fields.next().ok_or(DecodeError::Malformed)?;
+
+        let id = match tag {
+            "queue" => CustomId::Queue(fields.next().and_then(Role::from_value).ok_or(DecodeError::Malformed)?),
+            "cancel" => CustomId::Cancel(parse_id(fields.next())?),
+            "end" => CustomId::End(parse_token(fields.next())?),
+            "replace" => CustomId::Replace,
+            "requeue" => CustomId::Requeue(parse_id(fields.next())?),
+            "together" => CustomId::Together(parse_id(fields.next())?),
+            "targets" => CustomId::Targets(parse_id(fields.next())?),
+            "draft" => CustomId::Report(parse_token(fields.next())?),
+            "draftmodal" => CustomId::ReportModal(parse_token(fields.next())?),
+            // Reports used to carry their targets in the id rather than a draft token.
+            "report" | "reportmodal" => return Err(DecodeError::Stale),
+            "reportreason" => CustomId::ReportReason,
+            _ => return Err(DecodeError::Malformed),
+        };
+
+        match fields.next() {
+            Some(_) => Err(DecodeError::Malformed),
+            None => Ok(id),
+        }
+    }
+
+    /// Buttons on boards posted before custom ids were versioned, e.g. `QueueB-<random>`.
+    fn decode_board(custom_id: &str) -> Option<Role> {
+        let (prefix, _) = custom_id.split_once('-')?;
+
+        match prefix {
+            "QueueA" => Some(Role::Coop),
+            "QueueB" => Some(Role::Carry),
+            "QueueC" => Some(Role::Assist),
+            _ => None,
+        }
+    }
+}
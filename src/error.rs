@@ -0,0 +1,191 @@
+use rand::distributions::{Alphanumeric, DistString};
+use twilight_http::{error::ErrorType, response::DeserializeBodyError};
+use twilight_interactions::error::ParseError;
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::message::{MessageFlags, embed::Embed},
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    id::{
+        Id,
+        marker::{
+            ApplicationMarker,
+            InteractionMarker,
+        }
+    },
+};
+use twilight_util::builder::{
+    InteractionResponseDataBuilder,
+    embed::{EmbedBuilder, EmbedFooterBuilder},
+};
+use twilight_validate::{channel::ChannelValidationError, message::MessageValidationError};
+
+use crate::Bot;
+use crate::custom_id::DecodeError;
+use crate::permissions::denied_embed;
+
+/// Everything that can go wrong while handling a gateway event.
+#[derive(Debug, thiserror::Error)]
+pub enum BotError {
+    /// A failure caused by how the bot was used, shown to the user as is.
+    #[error("{0}")]
+    User(String),
+    /// The user lacks a moderator role for what they tried.
+    #[error("permission denied")]
+    PermissionDenied,
+    #[error("invalid custom id: {0}")]
+    CustomId(#[from] DecodeError),
+    /// Discord sent an interaction or event without a field the handler relies on.
+    #[error("missing {0}")]
+    Missing(&'static str),
+    #[error("could not parse command: {0}")]
+    Parse(#[from] ParseError),
+    /// Boxed, as it is several times the size of every other variant.
+    #[error("discord request failed: {0}")]
+    Http(Box<twilight_http::Error>),
+    #[error("could not read discord response: {0}")]
+    Body(#[from] DeserializeBodyError),
+    #[error("invalid message: {0}")]
+    Message(#[from] MessageValidationError),
+    #[error("invalid channel: {0}")]
+    Channel(#[from] ChannelValidationError),
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+    /// An environment variable is missing or invalid.
+    #[error("{0} is not configured")]
+    Config(&'static str),
+    /// Stored state that contradicts itself.
+    #[error("{0}")]
+    Inconsistent(&'static str),
+}
+
+impl From<twilight_http::Error> for BotError {
+    fn from(error: twilight_http::Error) -> Self {
+        BotError::Http(Box::new(error))
+    }
+}
+
+impl BotError {
+    pub fn user(description: &str) -> Self {
+        BotError::User(description.to_owned())
+    }
+}
+
+pub type Result<T, E = BotError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    User(String),
+    Permission,
+    /// Discord refused the request because the bot is missing permissions.
+    BotPermission,
+    Discord,
+    Database,
+    Internal,
+}
+
+impl ErrorKind {
+    pub fn classify(error: &BotError) -> Self {
+        match error {
+            BotError::User(description) => ErrorKind::User(description.clone()),
+            BotError::CustomId(error) => ErrorKind::User(error.description().to_owned()),
+            BotError::PermissionDenied => ErrorKind::Permission,
+            BotError::Http(error) => match error.kind() {
+                ErrorType::Response { status, .. } if status.get() == 403 => ErrorKind::BotPermission,
+                _ => ErrorKind::Discord,
+            },
+            BotError::Body(_) => ErrorKind::Discord,
+            BotError::Database(_) => ErrorKind::Database,
+            BotError::Missing(_)
+            | BotError::Parse(_)
+            | BotError::Message(_)
+            | BotError::Channel(_)
+            | BotError::Config(_)
+            | BotError::Inconsistent(_) => ErrorKind::Internal,
+        }
+    }
+
+    /// Whether the failure is worth an error in the logs rather than a warning.
+    fn is_fault(&self) -> bool {
+        !matches!(self, ErrorKind::User(_) | ErrorKind::Permission)
+    }
+
+    fn embed(&self, correlation: &str) -> Embed {
+        let error = |color: u32, title: &str, description: &str| EmbedBuilder::new()
+            .color(color)
+            .title(title)
+            .description(description)
+            .build();
+
+        let mut embed = match self {
+            ErrorKind::User(description) => error(0xFFE4C4, "Error", description.as_str()),
+            ErrorKind::Permission => denied_embed(),
+            ErrorKind::BotPermission => error(0xEE4B2B, "Missing permissions", "I don't have permission to do that here. Please ask an administrator to check my role."),
+            ErrorKind::Discord => error(0xEE4B2B, "Error", "Discord could not complete this request. Please try again in a moment."),
+            ErrorKind::Database => error(0xEE4B2B, "Error", "Something went wrong while loading or saving data. Please try again later."),
+            ErrorKind::Internal => error(0xEE4B2B, "Error", "Something went wrong while handling this interaction."),
+        };
+
+        embed.footer = Some(EmbedFooterBuilder::new(format!("Reference: {}", correlation)).build());
+
+        embed
+    }
+}
+
+/// What is needed to answer an interaction after its handler has consumed it.
+pub struct Reply {
+    application_id: Id<ApplicationMarker>,
+    id: Id<InteractionMarker>,
+    token: String,
+}
+
+impl Reply {
+    pub fn new(interaction: &Interaction) -> Self {
+        Self {
+            application_id: interaction.application_id,
+            id: interaction.id,
+            token: interaction.token.clone(),
+        }
+    }
+}
+
+impl Bot {
+    /// Logs a failed interaction under a correlation id and tells the user what happened.
+    pub async fn fail_interaction(&self, reply: Reply, error: BotError) {
+        let correlation = Alphanumeric.sample_string(&mut rand::thread_rng(), 8);
+        let kind = ErrorKind::classify(&error);
+
+        if kind.is_fault() {
+            tracing::error!(%correlation, ?kind, ?error, "error while handling interaction");
+        } else {
+            tracing::warn!(%correlation, ?kind, ?error, "interaction refused");
+        }
+
+        let embed = kind.embed(&correlation);
+        let client = self.client.interaction(reply.application_id);
+
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(InteractionResponseDataBuilder::new()
+                       .embeds([embed.clone()])
+                       .flags(MessageFlags::EPHEMERAL)
+                       .build()),
+        };
+
+        // The handler may already have responded or deferred, in which case only a followup works.
+        if client.create_response(reply.id, &reply.token, &response).await.is_ok() {
+            return;
+        }
+
+        let followup = match client.create_followup(&reply.token).embeds(&[embed]) {
+            Ok(followup) => followup.flags(MessageFlags::EPHEMERAL).await,
+            Err(error) => {
+                tracing::error!(%correlation, ?error, "could not build error reply");
+                return;
+            },
+        };
+
+        if let Err(error) = followup {
+            tracing::error!(%correlation, ?error, "could not send error reply");
+        }
+    }
+}
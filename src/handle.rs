@@ -1,28 +1,18 @@
-use std::{mem, sync::Arc};
+use std::mem;
 
-use anyhow::{bail, Context};
 use twilight_gateway::Event;
-use twilight_http::Client;
 use twilight_model::{
     application::interaction::{
-        application_command::CommandData,
-        message_component::MessageComponentInteractionData,
         Interaction,
         InteractionData,
         InteractionType,
-        modal::ModalInteractionData,
-    },
-    channel::{Channel, message::component::{ComponentType, ActionRow, Component}},
-    id::{
-        Id,
-        marker::{
-            UserMarker,
-        }
     },
+    channel::Channel,
 };
 
-use crate::interactions::{ping, setup, queue, end, rep};
 use crate::Bot;
+use crate::error::{BotError, Reply, Result};
+use crate::registry::ComponentData;
 
 impl Bot {
     pub async fn process(
@@ -36,8 +26,15 @@ impl Bot {
                     tracing::error!(?error, "error while handling thread update");
                 };
             },
-            _ => return,
-        };
+            Event::ThreadMembersUpdate(update) => {
+                for user in &update.removed_member_ids {
+                    if let Err(error) = self.abandon_session(update.id, *user).await {
+                        tracing::error!(?error, "error while handling thread members update");
+                    }
+                }
+            },
+            _ => (),
+        }
     }
 
     async fn interaction_create(&self, mut interaction: Interaction) {
@@ -46,12 +43,13 @@ impl Bot {
                 let data = if let Some(InteractionData::ApplicationCommand(data)) = mem::take(&mut interaction.data) {
                     *data
                 } else {
-                    tracing::error!("Data could not be unpacked as CommandData");
+                    self.fail_interaction(Reply::new(&interaction), BotError::Missing("command data")).await;
                     return;
                 };
 
-                if let Err(error) = self.handle_command(interaction, data).await {
-                    tracing::error!(?error, "error while handling command");
+                let reply = Reply::new(&interaction);
+                if let Err(error) = self.dispatch_command(interaction, data).await {
+                    self.fail_interaction(reply, error).await;
                 }
 
             }
@@ -59,122 +57,63 @@ impl Bot {
                 let data = if let Some(InteractionData::MessageComponent(data)) = mem::take(&mut interaction.data) {
                     data
                 } else {
-                    tracing::error!("Data could not be unpacked as MessageComponentInteractionData");
+                    self.fail_interaction(Reply::new(&interaction), BotError::Missing("component data")).await;
                     return;
                 };
 
-                if let Err(error) = self.handle_interaction(interaction, data).await {
-                    tracing::error!(?error, "error while handling interaction");
+                let reply = Reply::new(&interaction);
+                if let Err(error) = self.dispatch_component(interaction, ComponentData::Message(data)).await {
+                    self.fail_interaction(reply, error).await;
                 }
             }
             InteractionType::ModalSubmit => {
                 let data = if let Some(InteractionData::ModalSubmit(data)) = mem::take(&mut interaction.data) {
                     data
                 } else {
-                    tracing::error!("Data could not be unpacked as MessageComponentInteractionData");
+                    self.fail_interaction(Reply::new(&interaction), BotError::Missing("modal data")).await;
                     return;
                 };
 
-                if let Err(error) = self.handle_modal(interaction, data).await {
-                    tracing::error!(?error, "error while handling interaction");
+                let reply = Reply::new(&interaction);
+                if let Err(error) = self.dispatch_component(interaction, ComponentData::Modal(data)).await {
+                    self.fail_interaction(reply, error).await;
                 }
             }
             _ => {
                 tracing::warn!("ignoring autocomplete interaction");
-                return;
             }
-        };
+        }
 
     }
 
-    async fn thread_update(&self, channel: Channel) -> anyhow::Result<()> {
-        if channel.thread_metadata.clone().unwrap().archived {
-            if self.is_thread(channel.id).await?.unwrap() {
-                let users = self.get_thread(channel.id).await?.unwrap();
-                let _ = self.handle_rep(users, channel).await;
-            }
+    pub async fn thread_update(&self, channel: Channel) -> Result<()> {
+        let archived = channel.thread_metadata.as_ref().is_some_and(|i| i.archived);
+
+        if archived {
+            self.finish_session(channel).await?;
         }
 
         Ok(())
     }
 
-    async fn handle_command(
-        &self,
-        interaction: Interaction,
-        data: CommandData,
-    ) -> anyhow::Result<()> {
-        match &*data.name {
-            ping::NAME => ping::Ping::handle(interaction, data, self).await,
-            setup::NAME => setup::Setup::handle(interaction, data, self).await,
-            end::NAME => end::End::handle(interaction, data, self).await,
-            name => bail!("unknown command: {}", name),
-        }
-    }
-
-    async fn handle_interaction(
-        &self,
-        interaction: Interaction,
-        data: MessageComponentInteractionData,
-    ) -> anyhow::Result<()> {
-        let id = Some(data.clone().custom_id);
-        match data.component_type{
-            ComponentType::Button => {
-
-                let component_number = if let Component::ActionRow (ActionRow { components: arr }) = &interaction.message.as_ref().unwrap()
-                                    .components[0] {
-                    arr.iter().position(|i| {
-                        match i {
-                            Component::Button(button) => button.custom_id == id,
-                            _ => false
-                        }
-                    })
-                } else { None };
-
-                let id = id.unwrap();
-
-                match id {
-                    _ if id.starts_with("Queue") => self.handle_queue_button(interaction, component_number).await,
-                    _ if id.starts_with("End") => end::End::handle_confirm(interaction, self).await,
-                    _ if id.starts_with("Cancel") => queue::Queue::handle_cancel(interaction, self).await, // bruh
-                    _ => bail!("button not implemented"),
-                }
-            },
-            ComponentType::SelectMenu => {
-                let id = id.unwrap();
-                match id {
-                    _ if id.starts_with("Report") => rep::handle_report(interaction, self, data.values.as_slice()).await,
-                    _ => bail!("Select Menu not implemented"),
-                }
-            },
-            _ => bail!("ignoring interaction"),
-        }
-    }
+    /// Awards rep and sends the report polls for a session thread. Rep is awarded in the same write
+    /// that claims the thread, so an archive event and a reconciliation pass seeing the same thread
+    /// only finish it once, and a failure leaves it to be finished again.
+    pub async fn finish_session(&self, channel: Channel) -> Result<()> {
+        let Some(session) = self.storage.get_session(channel.id).await? else {
+            return Ok(());
+        };
 
-    async fn handle_modal(
-        &self,
-        interaction: Interaction,
-        data: ModalInteractionData,
-    ) -> anyhow::Result<()> {
-        let id = Some(data.clone().custom_id).unwrap();
-        match id {
-            _ if id.starts_with("Modal") => rep::handle_text(interaction, data, self).await,
-            _ => bail!("Modal type not implemented"),
+        if !self.award_rep(&session, &channel).await? {
+            return Ok(());
         }
-    }
 
-    async fn handle_queue_button(
-        &self,
-        interaction: Interaction,
-        component_number: Option<usize>
-    ) -> anyhow::Result<()> {
-
-        match component_number {
-            Some(0) => queue::Queue::handle_queue_a(interaction, self).await,
-            Some(1) => queue::Queue::handle_queue_b(interaction, self).await,
-            Some(2) => queue::Queue::handle_queue_c(interaction, self).await,
-            Some(_) => bail!("Shouldn't have more than 3 buttons"),
-            None => bail!("Shouldn't be possible"),
+        // Rep is in by now, so finishing again would award it twice. A failed poll is only logged.
+        let id = channel.id;
+        if let Err(error) = self.send_polls(session, channel).await {
+            tracing::error!(?error, channel = id.get(), "failed to send report polls");
         }
 
+        Ok(())
     }
 }
@@ -0,0 +1,109 @@
+use twilight_interactions::command::{CommandOption, CreateOption};
+use twilight_model::channel::message::embed::Embed;
+use twilight_util::builder::embed::EmbedBuilder;
+
+use crate::matchmaking::Role;
+
+/// What a queue board is used for. Decides its buttons, group sizes, thread names and rep.
+#[derive(CommandOption, CreateOption, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoardKind {
+    #[option(name = "Elite farming", value = "elite")]
+    Elite,
+    #[option(name = "Overlord farming", value = "overlord")]
+    Overlord,
+    #[option(name = "Weekly boss", value = "weekly")]
+    Weekly,
+    #[option(name = "Free co-op", value = "coop")]
+    FreeCoop,
+}
+
+impl BoardKind {
+    /// Reads back the derived `value`, which is what the `boards` table stores. Boards stored
+    /// before kinds existed are elite boards.
+    pub fn from_value(value: &str) -> Self {
+        match value {
+            "overlord" => BoardKind::Overlord,
+            "weekly" => BoardKind::Weekly,
+            "coop" => BoardKind::FreeCoop,
+            _ => BoardKind::Elite,
+        }
+    }
+
+    /// The queues offered on the board, in button order.
+    pub fn roles(&self) -> &'static [Role] {
+        match self {
+            BoardKind::Elite | BoardKind::Overlord => &[Role::Coop, Role::Carry, Role::Assist],
+            BoardKind::Weekly => &[Role::Carry, Role::Assist],
+            BoardKind::FreeCoop => &[Role::Coop],
+        }
+    }
+
+    /// How many players a co-op group needs. Carry groups are always two carries and an assist.
+    pub fn coop_size(&self) -> usize {
+        match self {
+            BoardKind::FreeCoop => 2,
+            _ => 3,
+        }
+    }
+
+    pub fn thread_name(&self) -> &'static str {
+        match self {
+            BoardKind::Elite => "Echos Farming Thread",
+            BoardKind::Overlord => "Overlord Farming Thread",
+            BoardKind::Weekly => "Weekly Boss Thread",
+            BoardKind::FreeCoop => "Co-op Thread",
+        }
+    }
+
+    /// Reputation awarded to a member of a finished session.
+    pub fn rep(&self, role: Role) -> i32 {
+        match (self, role) {
+            (BoardKind::Elite, Role::Coop) => 1,
+            (BoardKind::Elite, Role::Assist) => 2,
+            (BoardKind::Overlord, Role::Coop) => 1,
+            (BoardKind::Overlord, Role::Assist) => 3,
+            (BoardKind::Weekly, Role::Carry) => 1,
+            (BoardKind::Weekly, Role::Assist) => 3,
+            _ => 0,
+        }
+    }
+
+    pub fn embed(&self) -> Embed {
+        let description = match self {
+            BoardKind::Elite => "This is a queue bot for multiplayer Echoes farming in Wuthering Waves.
+
+Choose a queue based on your needs for the multiplayer session :-
+1. **Co-op (1)**: If you want to share elites with others who are also willing to share their elites. (+1 reputation)
+2. **Carry (2)**: If you are willing to help fight elites for others in their worlds.
+3. **Assist (3)**: If you need help in fighting elites in your own world. (+2 reputation)
+
+**Assist (3)** is recommended for people who just want to farm their own world, whereas **Co-op (1)** would suit those better who are just starting the farming session and also want to farm other people's worlds alongside their own. **Carry (2)** works best for those who have farmed their own world already but still want more echoes.
+
+Don’t forget to poll at the end of each multiplayer session for reputation points! These points will be useful soon…",
+            BoardKind::Overlord => "This is a queue bot for multiplayer Overlord farming in Wuthering Waves.
+
+Choose a queue based on your needs for the multiplayer session :-
+1. **Co-op (1)**: If you want to take turns clearing each other's Overlords. (+1 reputation)
+2. **Carry (2)**: If you are willing to help fight Overlords in other people's worlds.
+3. **Assist (3)**: If you need help with an Overlord in your own world. (+3 reputation)
+
+Don’t forget to poll at the end of each multiplayer session for reputation points!",
+            BoardKind::Weekly => "This is a queue bot for Weekly Boss challenges in Wuthering Waves.
+
+Choose a queue based on your needs for the multiplayer session :-
+2. **Carry (2)**: If you are willing to help clear Weekly Bosses in other people's worlds. (+1 reputation)
+3. **Assist (3)**: If you need help clearing a Weekly Boss in your own world. (+3 reputation)
+
+Don’t forget to poll at the end of each multiplayer session for reputation points!",
+            BoardKind::FreeCoop => "This is a queue bot for casual co-op in Wuthering Waves.
+
+Press **Co-op (1)** to be paired with one other player for anything you like, from exploration to events. No reputation is awarded on this board.",
+        };
+
+        EmbedBuilder::new()
+            .color(0x63c5da)
+            .title("Queue")
+            .description(description)
+            .build()
+    }
+}
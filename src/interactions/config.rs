@@ -0,0 +1,126 @@
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::{
+    application::interaction::{application_command::CommandData, Interaction},
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    channel::message::MessageFlags,
+    guild::Permissions,
+    id::{
+        Id,
+        marker::RoleMarker,
+    },
+};
+use twilight_util::builder::{
+    InteractionResponseDataBuilder,
+    embed::EmbedBuilder,
+};
+
+use crate::Bot;
+use crate::error::{BotError, Result};
+use crate::permissions::Permission;
+use crate::registry::{HandlerFuture, SlashCommand};
+
+pub const NAME: &str = "config";
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "config", desc = "configure the bot for this server", default_permissions = "admin_perms", dm_permission = false)]
+pub enum ConfigCommand {
+    #[command(name = "modrole")]
+    ModRole(ModRole),
+}
+
+fn admin_perms() -> Permissions {
+    Permissions::ADMINISTRATOR
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "modrole", desc = "choose which roles can moderate the bot")]
+pub enum ModRole {
+    #[command(name = "add")]
+    Add(ModRoleAdd),
+    #[command(name = "remove")]
+    Remove(ModRoleRemove),
+    #[command(name = "list")]
+    List(ModRoleList),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "add", desc = "allow a role to use moderator commands")]
+pub struct ModRoleAdd {
+    /// Role to allow
+    role: Id<RoleMarker>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "remove", desc = "stop a role from using moderator commands")]
+pub struct ModRoleRemove {
+    /// Role to remove
+    role: Id<RoleMarker>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "list", desc = "list the moderator roles")]
+pub struct ModRoleList;
+
+impl ConfigCommand {
+    pub async fn handle(
+        interaction: Interaction,
+        data: CommandData,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+        let guild_id = interaction.guild_id.ok_or_else(|| BotError::user("Config can only be used in a server."))?;
+
+        let ConfigCommand::ModRole(command) = ConfigCommand::from_interaction(data.into())?;
+
+        let description = match command {
+            ModRole::Add(add) => if bot.storage.add_mod_role(guild_id, add.role).await? {
+                format!("<@&{}> can now use moderator commands.", add.role.get())
+            } else {
+                format!("<@&{}> is already a moderator role.", add.role.get())
+            },
+            ModRole::Remove(remove) => if bot.storage.remove_mod_role(guild_id, remove.role).await? {
+                format!("<@&{}> can no longer use moderator commands.", remove.role.get())
+            } else {
+                format!("<@&{}> is not a moderator role.", remove.role.get())
+            },
+            ModRole::List(_) => {
+                let roles = bot.storage.get_mod_roles(guild_id).await?;
+                if roles.is_empty() {
+                    "No moderator roles are set. Only administrators can use moderator commands.".to_owned()
+                } else {
+                    roles.iter().map(|i| format!("<@&{}>", i.get())).collect::<Vec<String>>().join("\n")
+                }
+            },
+        };
+
+        let embed = EmbedBuilder::new()
+            .color(0x50C878)
+            .title("Moderator roles")
+            .description(description)
+            .build();
+
+        let data = InteractionResponseDataBuilder::new()
+            .embeds([embed])
+            .flags(MessageFlags::EPHEMERAL)
+            .build();
+
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(data),
+        };
+
+        client.create_response(interaction.id, &interaction.token, &response).await?;
+
+        Ok(())
+    }
+}
+
+impl SlashCommand for ConfigCommand {
+    const NAME: &'static str = NAME;
+    // Only administrators may hand out moderator access.
+    const PERMISSION: Permission = Permission::Administrator;
+
+    fn handle(interaction: Interaction, data: CommandData, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(Self::handle(interaction, data, bot))
+    }
+}
@@ -1,4 +1,3 @@
-use anyhow::Context;
 use twilight_interactions::command::{CommandModel, CreateCommand};
 use twilight_model::{
     application::interaction::{application_command::CommandData, Interaction},
@@ -9,7 +8,8 @@ use twilight_model::{
             ActionRow,
             Button,
             ButtonStyle,
-            Component
+            Component,
+            ComponentType,
         },
     }
 };
@@ -19,9 +19,12 @@ use twilight_util::builder::{
 };
 
 use rand::distributions::{Alphanumeric, DistString};
-use regex::Regex;
 
 use crate::Bot;
+use crate::error::{BotError, Result};
+use crate::custom_id::{CustomId, DecodeError};
+use crate::interactions::{queue::Queue, requeue::Requeue};
+use crate::registry::{ComponentData, ComponentHandler, HandlerFuture, SlashCommand};
 
 pub const NAME: &str = "end";
 
@@ -32,12 +35,10 @@ pub struct End;
 
 impl End {
 
-    pub fn get_action_row(disabled: bool) -> Component {
-        let id = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
-
+    pub fn get_action_row(id: &str, disabled: bool) -> Component {
         Component::ActionRow ( ActionRow {
             components: Vec::from([Component::Button(Button {
-                custom_id: Some(format!("End-{}", id).to_owned()),
+                custom_id: Some(CustomId::End(id.to_owned()).encode()),
                 disabled,
                 emoji: None,
                 label: Some("Confirm".to_owned()),
@@ -52,12 +53,13 @@ impl End {
         interaction: Interaction,
         _data: CommandData,
         bot: &Bot,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let client = bot.client.interaction(interaction.application_id);
 
-        let channel = interaction.channel.clone().context("Could not get message channel. Is this in a channel?")?;
+        let channel = interaction.channel.clone().ok_or_else(|| BotError::user("Could not get message channel. Is this in a channel?"))?;
+        let user_invoke = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
 
-        if !bot.is_thread(channel.id).await.unwrap().unwrap() {
+        if !bot.storage.is_thread(channel.id).await? {
             let embed = EmbedBuilder::new()
                 .color(0xEE4B2B)
                 .title("Error")
@@ -78,21 +80,14 @@ impl End {
 
             return Ok(());
         }
-        /*
-        let acknolewedge = InteractionResponse {
-            kind: InteractionResponseType::DeferredChannelMessageWithSource,
-            data: Some(InteractionResponseDataBuilder::new()
-                       .build()),
-        };
 
-        client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
-        */
-
-        let mut users = bot.get_thread(channel.id).await?.unwrap();
-        let user_invoke = interaction.author_id().unwrap();
+        let mut users = bot.storage.get_thread(channel.id).await?.ok_or(BotError::Inconsistent("queue thread was removed while ending it"))?;
 
         users.retain(|i| *i != user_invoke);
 
+        let request = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+        bot.storage.set_end_request(channel.id, &request, user_invoke).await?;
+
         let ping = users.into_iter().map(|i| format!("<@{}> ", i)).collect::<String>();
 
         let embed = EmbedBuilder::new()
@@ -104,7 +99,7 @@ impl End {
         let data = InteractionResponseDataBuilder::new()
                       .content(ping.as_str())
                        .embeds([embed])
-                       .components([Self::get_action_row(false)])
+                       .components([Self::get_action_row(&request, false)])
                        .build();
 
         let acknolewedge = InteractionResponse {
@@ -113,61 +108,92 @@ impl End {
         };
 
         client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
-        /*
-        client.create_followup(&interaction.token)
-              .content(ping.as_str())?
-              .embeds(&[embed])?
-              .components(&[Self::get_action_row(false)])?
-              .await?;
-        */
 
         Ok(())
     }
 
+    fn error_response(description: &str) -> InteractionResponse {
+        let embed = EmbedBuilder::new()
+            .color(0xEE4B2B)
+            .title("Error")
+            .description(description)
+            .build();
+
+        let data = InteractionResponseDataBuilder::new()
+                       .flags(MessageFlags::EPHEMERAL)
+                       .embeds([embed])
+                       .build();
+
+        InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(data),
+        }
+    }
+
     pub async fn handle_confirm (
         interaction: Interaction,
         bot: &Bot,
-    ) -> anyhow::Result<()> {
+        request: &str,
+    ) -> Result<()> {
         let client = bot.client.interaction(interaction.application_id);
 
-        let message = interaction.message.clone().unwrap();
+        let channel = interaction.channel.clone().ok_or_else(|| BotError::user("Could not get message channel. Is this in a channel?"))?;
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
 
-        let valid: Vec<u64> = Regex::new(r"\d+").unwrap()
-            .find_iter(message.content.as_str())
-            .map(|i| i.as_str().parse::<u64>().unwrap())
-            .collect::<Vec<u64>>(); //what the f-
+        let Some(users) = bot.storage.get_thread(channel.id).await? else {
+            let response = Self::error_response("This session has already ended.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
 
-        if !valid.contains(&interaction.author_id().unwrap().get()) {
-            let embed = EmbedBuilder::new()
-                .color(0xEE4B2B)
-                .title("Error")
-                .description("You do not have permission to confirm this request.")
-                .build();
+            return Ok(());
+        };
 
-            let data = InteractionResponseDataBuilder::new()
-                           .flags(MessageFlags::EPHEMERAL)
-                           .embeds([embed])
-                           .build();
+        if !users.contains(&author) {
+            let response = Self::error_response("You do not have permission to confirm this request.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
 
-            let acknolewedge = InteractionResponse {
-                kind: InteractionResponseType::ChannelMessageWithSource,
-                data: Some(data),
-            };
+            return Ok(());
+        }
+
+        let requester = match bot.storage.get_end_request(channel.id).await? {
+            Some((current, requester)) if current == request => requester,
+            _ => {
+                let response = Self::error_response("This end request is no longer valid. Please use /end again.");
+                client.create_response(interaction.id, &interaction.token, &response).await?;
 
-            client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
+                return Ok(());
+            }
+        };
+
+        if requester == author {
+            let response = Self::error_response("Another member of this session needs to confirm your request.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
 
             return Ok(());
         }
 
+        let requeue = match bot.storage.get_session(channel.id).await? {
+            Some(session) => bot.register_requeue(channel.id,
+                                                  channel.parent_id,
+                                                  session.board,
+                                                  Queue::thread_queue_type(&channel),
+                                                  session.users).await,
+            None => false,
+        };
+
         let embed = EmbedBuilder::new()
             .color(0x50C878)
             .title("Confirmed")
             .description("Closing this thread.")
             .build();
 
-        let data = InteractionResponseDataBuilder::new()
-                       .embeds([embed])
-                       .build();
+        let mut data = InteractionResponseDataBuilder::new()
+                       .embeds([embed]);
+
+        if requeue {
+            data = data.components([Requeue::get_action_row(channel.id, false)]);
+        }
+
+        let data = data.build();
 
         let acknolewedge = InteractionResponse {
             kind: InteractionResponseType::ChannelMessageWithSource,
@@ -176,7 +202,7 @@ impl End {
 
         client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
 
-        bot.client.update_thread(interaction.channel.unwrap().id)
+        bot.client.update_thread(channel.id)
                   .archived(true)
                   .locked(true)
                   .await?;
@@ -184,3 +210,24 @@ impl End {
     }
 }
 
+
+impl SlashCommand for End {
+    const NAME: &'static str = NAME;
+
+    fn handle(interaction: Interaction, data: CommandData, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(Self::handle(interaction, data, bot))
+    }
+}
+
+impl ComponentHandler for End {
+    const TAGS: &'static [&'static str] = &["end"];
+
+    fn handle(interaction: Interaction, data: ComponentData, id: CustomId, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(async move {
+            match (data.component_type(), id) {
+                (Some(ComponentType::Button), CustomId::End(request)) => Self::handle_confirm(interaction, bot, &request).await,
+                _ => Err(DecodeError::Malformed.into()),
+            }
+        })
+    }
+}
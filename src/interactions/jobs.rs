@@ -0,0 +1,131 @@
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::{
+    application::interaction::{application_command::CommandData, Interaction},
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    channel::message::MessageFlags,
+    guild::Permissions,
+};
+use twilight_util::builder::{
+    InteractionResponseDataBuilder,
+    embed::EmbedBuilder,
+};
+
+use crate::Bot;
+use crate::error::{BotError, Result};
+use crate::permissions::Permission;
+use crate::registry::{HandlerFuture, SlashCommand};
+use crate::scheduler::unix_now;
+use crate::storage::Job;
+
+pub const NAME: &str = "jobs";
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "jobs", desc = "inspect the bot's scheduled jobs", default_permissions = "admin_perms", dm_permission = false)]
+pub enum JobsCommand {
+    #[command(name = "list")]
+    List(JobsList),
+    #[command(name = "cancel")]
+    Cancel(JobsCancel),
+}
+
+fn admin_perms() -> Permissions {
+    Permissions::ADMINISTRATOR
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "list", desc = "list the scheduled jobs")]
+pub struct JobsList;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "cancel", desc = "stop a job from running again")]
+pub struct JobsCancel {
+    /// Id of the job, as shown in the list
+    id: i64,
+}
+
+/// One line per job, with when it runs next and how its last run went.
+fn describe(job: &Job, now: i64) -> String {
+    let mut line = format!("`{}` **{}** ({}) <t:{}:R>", job.id, job.name, job.kind, job.run_at);
+
+    if let Some(interval) = job.interval {
+        line.push_str(&format!(", every {} minutes", interval / 60));
+    }
+    if job.lease_until.is_some_and(|i| i > now) {
+        line.push_str(", running now");
+    }
+    if let Some(error) = &job.last_error {
+        line.push_str(&format!("\nLast run failed: {}", error));
+    }
+
+    line
+}
+
+impl JobsCommand {
+    pub async fn handle(
+        interaction: Interaction,
+        data: CommandData,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+
+        let description = match JobsCommand::from_interaction(data.into())? {
+            JobsCommand::List(_) => {
+                let jobs = bot.storage.get_jobs().await?;
+                if jobs.is_empty() {
+                    "No jobs are scheduled.".to_owned()
+                } else {
+                    // Stays under the embed description limit, however many jobs there are.
+                    let now = unix_now();
+                    let mut description = String::new();
+                    for (index, job) in jobs.iter().enumerate() {
+                        let line = describe(job, now);
+                        if description.len() + line.len() > 3900 {
+                            description.push_str(&format!("...and {} more.", jobs.len() - index));
+                            break;
+                        }
+
+                        description.push_str(&line);
+                        description.push('\n');
+                    }
+
+                    description
+                }
+            },
+            JobsCommand::Cancel(cancel) => if bot.storage.cancel_job(cancel.id).await? {
+                format!("Job `{}` was cancelled.", cancel.id)
+            } else {
+                return Err(BotError::user(&format!("There is no job with id `{}`.", cancel.id)));
+            },
+        };
+
+        let embed = EmbedBuilder::new()
+            .color(0x50C878)
+            .title("Scheduled jobs")
+            .description(description)
+            .build();
+
+        let data = InteractionResponseDataBuilder::new()
+            .embeds([embed])
+            .flags(MessageFlags::EPHEMERAL)
+            .build();
+
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(data),
+        };
+
+        client.create_response(interaction.id, &interaction.token, &response).await?;
+
+        Ok(())
+    }
+}
+
+impl SlashCommand for JobsCommand {
+    const NAME: &'static str = NAME;
+    // Jobs act for every server the bot is in.
+    const PERMISSION: Permission = Permission::Administrator;
+
+    fn handle(interaction: Interaction, data: CommandData, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(Self::handle(interaction, data, bot))
+    }
+}
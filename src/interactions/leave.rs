@@ -0,0 +1,290 @@
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::{
+    application::interaction::{application_command::CommandData, Interaction},
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    channel::message::{
+        MessageFlags,
+        component::{
+            ActionRow,
+            Button,
+            ButtonStyle,
+            Component,
+            ComponentType,
+        },
+    },
+    id::{
+        Id,
+        marker::{
+            ChannelMarker,
+            UserMarker,
+        }
+    },
+};
+use twilight_util::builder::{
+    InteractionResponseDataBuilder,
+    embed::EmbedBuilder,
+};
+
+use crate::Bot;
+use crate::error::{BotError, Result};
+use crate::custom_id::{CustomId, DecodeError};
+use crate::interactions::{profile::describe_member, queue::Queue};
+use crate::matchmaking::Role;
+use crate::registry::{ComponentData, ComponentHandler, HandlerFuture, SlashCommand};
+
+pub const NAME: &str = "leave-session";
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "leave-session", desc = "leave the current session thread")]
+pub struct Leave;
+
+/// Rep taken from a member who leaves a session early, from `ABANDON_PENALTY`.
+pub fn abandon_penalty() -> i32 {
+    std::env::var("ABANDON_PENALTY")
+        .ok()
+        .and_then(|i| i.parse::<i32>().ok())
+        .unwrap_or(0)
+}
+
+impl Leave {
+    fn get_action_row(disabled: bool) -> Component {
+        Component::ActionRow ( ActionRow {
+            components: Vec::from([Component::Button(Button {
+                custom_id: Some(CustomId::Replace.encode()),
+                disabled,
+                emoji: None,
+                label: Some("Find replacement".to_owned()),
+                style: ButtonStyle::Primary,
+                url: None,
+            }),
+            ]),
+        })
+    }
+
+    fn ephemeral(color: u32, title: &str, description: &str) -> InteractionResponse {
+        let embed = EmbedBuilder::new()
+            .color(color)
+            .title(title)
+            .description(description)
+            .build();
+
+        let data = InteractionResponseDataBuilder::new()
+                       .flags(MessageFlags::EPHEMERAL)
+                       .embeds([embed])
+                       .build();
+
+        InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(data),
+        }
+    }
+
+    pub async fn handle(
+        interaction: Interaction,
+        _data: CommandData,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+
+        let channel = interaction.channel.clone().ok_or_else(|| BotError::user("Could not get message channel. Is this in a channel?"))?;
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let is_member = match bot.storage.get_thread(channel.id).await? {
+            Some(users) => users.contains(&author),
+            None => false,
+        };
+
+        if !is_member {
+            let response = Self::ephemeral(0xEE4B2B, "Error", "You are not a member of an active session in this thread.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
+
+            return Ok(());
+        }
+
+        let response = Self::ephemeral(0x50C878, "Confirmed", "Leaving this session.");
+        client.create_response(interaction.id, &interaction.token, &response).await?;
+
+        bot.abandon_session(channel.id, author).await?;
+
+        // Triggers a thread members update, which is ignored now that the slot is empty.
+        bot.client.remove_thread_member(channel.id, author).await?;
+
+        Ok(())
+    }
+
+    pub async fn handle_replace(
+        interaction: Interaction,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+
+        let channel = interaction.channel.clone().ok_or_else(|| BotError::user("Could not get message channel. Is this in a channel?"))?;
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let Some(session) = bot.storage.get_session(channel.id).await? else {
+            let response = Self::ephemeral(0xEE4B2B, "Error", "This session has already ended.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
+
+            return Ok(());
+        };
+
+        if !session.users.contains(&Some(author)) {
+            let response = Self::ephemeral(0xEE4B2B, "Error", "You are not a member of this session.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
+
+            return Ok(());
+        }
+
+        let (Some(board), Some(queue_type)) = (session.board, Queue::thread_queue_type(&channel)) else {
+            let response = Self::ephemeral(0xEE4B2B, "Error", "The queue for this session is no longer available.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
+
+            return Ok(());
+        };
+
+        let Some(slot) = session.users.iter().position(|i| i.is_none()) else {
+            let response = Self::ephemeral(0xFFE4C4, "Error", "This session is already full.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
+
+            return Ok(());
+        };
+
+        // Carries must be able to enter the assist's world, and a new assist's world must be enterable.
+        let mut levels = Vec::new();
+        for (index, user) in session.users.iter().enumerate() {
+            if let Some(user) = user {
+                levels.push((Role::for_slot(queue_type, index), bot.storage.get_profile(*user).await?.map_or(0, |i| i.world_level)));
+            }
+        }
+
+        let role = Role::for_slot(queue_type, slot);
+        let assist_level = levels.iter().filter(|(i, _)| *i == Role::Assist).map(|(_, level)| *level).max().unwrap_or(0);
+        let carry_level = levels.iter().filter(|(i, _)| *i == Role::Carry).map(|(_, level)| *level).min().unwrap_or(i32::MAX);
+
+        let (paused, replacement) = match bot.board(board).await {
+            Some(queue) => {
+                let mut queue = queue.lock().await;
+                match queue.paused {
+                    true => (true, None),
+                    false => (false, queue.queues.take_replacement(queue_type, slot, |level| match role {
+                        Role::Carry => level >= assist_level,
+                        Role::Assist => level <= carry_level,
+                        Role::Coop => true,
+                    })),
+                }
+            },
+            None => (false, None),
+        };
+
+        if paused {
+            let response = Self::ephemeral(0xFFE4C4, "Error", "This queue is paused for maintenance. Please try again later.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
+
+            return Ok(());
+        }
+
+        let Some(entry) = replacement else {
+            let response = Self::ephemeral(0xFFE4C4, "Error", "Nobody is waiting in that queue right now. Please try again later.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
+
+            return Ok(());
+        };
+        let replacement = entry.user;
+
+        // Another member may have filled the slot since it was read, so the replacement keeps their place.
+        if !bot.storage.fill_thread(channel.id, slot as i32, replacement).await? {
+            if let Some(queue) = bot.board(board).await {
+                queue.lock().await.queues.restore(role, entry);
+            }
+
+            let response = Self::ephemeral(0xFFE4C4, "Error", "This slot has already been filled.");
+            client.create_response(interaction.id, &interaction.token, &response).await?;
+
+            return Ok(());
+        }
+
+        let response = Self::ephemeral(0x50C878, "Success", "Found a replacement.");
+        client.create_response(interaction.id, &interaction.token, &response).await?;
+
+        if let Some(message) = &interaction.message {
+            bot.client.update_message(channel.id, message.id)
+                .components(Some(&[Self::get_action_row(true)]))?
+                .await?;
+        }
+
+        bot.client.add_thread_member(channel.id, replacement).await?;
+
+        let profile = bot.storage.get_profile(replacement).await?;
+        let member = describe_member(replacement, role.name(), profile.as_ref());
+
+        let embed = EmbedBuilder::new()
+            .color(0x63c5da)
+            .title("Welcome")
+            .description(format!("You have been pulled from the queue to replace a member who left this session. When you have finished, please use the command /end\n\n{}", member))
+            .build();
+
+        bot.client
+            .create_message(channel.id)
+            .embeds(&[embed])?
+            .content(format!("<@{}>", replacement.get()).as_str())?
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl Bot {
+    /// Marks a member as having abandoned the session and offers the rest a replacement.
+    pub async fn abandon_session(&self, channel_id: Id<ChannelMarker>, user_id: Id<UserMarker>) -> Result<()> {
+        if !self.storage.abandon_thread(channel_id, user_id, abandon_penalty()).await? {
+            return Ok(());
+        }
+
+        let remaining = self.storage.get_thread(channel_id).await?.unwrap_or_default();
+
+        if remaining.is_empty() {
+            self.storage.remove_thread(channel_id).await?;
+            self.client.update_thread(channel_id)
+                .archived(true)
+                .locked(true)
+                .await?;
+
+            return Ok(());
+        }
+
+        let embed = EmbedBuilder::new()
+            .color(0xFFE4C4)
+            .title("Member left")
+            .description(format!("<@{}> has left this session. Click below to pull a replacement from the queue.", user_id.get()))
+            .build();
+
+        self.client
+            .create_message(channel_id)
+            .embeds(&[embed])?
+            .components(&[Leave::get_action_row(false)])?
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl SlashCommand for Leave {
+    const NAME: &'static str = NAME;
+
+    fn handle(interaction: Interaction, data: CommandData, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(Self::handle(interaction, data, bot))
+    }
+}
+
+impl ComponentHandler for Leave {
+    const TAGS: &'static [&'static str] = &["replace"];
+
+    fn handle(interaction: Interaction, data: ComponentData, id: CustomId, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(async move {
+            match (data.component_type(), id) {
+                (Some(ComponentType::Button), CustomId::Replace) => Self::handle_replace(interaction, bot).await,
+                _ => Err(DecodeError::Malformed.into()),
+            }
+        })
+    }
+}
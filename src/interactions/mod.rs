@@ -1,5 +1,14 @@
+pub mod board;
 pub mod setup;
+pub mod config;
+pub mod jobs;
 pub mod ping;
 pub mod queue;
+pub mod queue_command;
 pub mod end;
 pub mod rep;
+pub mod rep_command;
+pub mod leave;
+pub mod requeue;
+pub mod profile;
+pub mod targets;
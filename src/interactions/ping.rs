@@ -1,4 +1,3 @@
-use anyhow::Context;
 use twilight_interactions::command::{CommandModel, CreateCommand};
 use twilight_model::{
     application::interaction::{application_command::CommandData, Interaction},
@@ -7,6 +6,8 @@ use twilight_model::{
 use twilight_util::builder::InteractionResponseDataBuilder;
 
 use crate::Bot;
+use crate::error::Result;
+use crate::registry::{HandlerFuture, SlashCommand};
 
 pub const NAME: &str = "ping";
 
@@ -20,7 +21,7 @@ impl Ping {
         interaction: Interaction,
         _data: CommandData,
         bot: &Bot,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let client = bot.client.interaction(interaction.application_id);
 
         let data = InteractionResponseDataBuilder::new()
@@ -38,3 +39,11 @@ impl Ping {
     }
 }
 
+
+impl SlashCommand for Ping {
+    const NAME: &'static str = NAME;
+
+    fn handle(interaction: Interaction, data: CommandData, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(Self::handle(interaction, data, bot))
+    }
+}
@@ -0,0 +1,170 @@
+use twilight_interactions::command::{CommandModel, CommandOption, CreateCommand, CreateOption};
+use twilight_model::{
+    application::interaction::{application_command::CommandData, Interaction},
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    channel::message::{MessageFlags, embed::Embed},
+    id::{
+        Id,
+        marker::UserMarker,
+    },
+};
+use twilight_util::builder::{
+    InteractionResponseDataBuilder,
+    embed::{EmbedBuilder, EmbedFieldBuilder},
+};
+
+use crate::Bot;
+use crate::error::{BotError, Result};
+use crate::storage::Profile;
+use crate::registry::{HandlerFuture, SlashCommand};
+
+pub const NAME: &str = "profile";
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "profile", desc = "share your in-game details with your groups")]
+pub enum ProfileCommand {
+    #[command(name = "set")]
+    Set(ProfileSet),
+    #[command(name = "view")]
+    View(ProfileView),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "set", desc = "set your in-game profile")]
+pub struct ProfileSet {
+    /// Your in-game UID
+    #[command(min_length = 9, max_length = 9)]
+    uid: String,
+    /// Your in-game name
+    #[command(max_length = 32)]
+    name: String,
+    /// Your current World Level
+    #[command(min_value = 0, max_value = 8)]
+    world_level: i64,
+    /// Your server region
+    region: Region,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "view", desc = "view a profile")]
+pub struct ProfileView {
+    /// User to view, defaults to yourself
+    user: Option<Id<UserMarker>>,
+}
+
+#[derive(CommandOption, CreateOption)]
+pub enum Region {
+    #[option(name = "America", value = "America")]
+    America,
+    #[option(name = "Europe", value = "Europe")]
+    Europe,
+    #[option(name = "Asia", value = "Asia")]
+    Asia,
+    #[option(name = "SEA", value = "SEA")]
+    Sea,
+    #[option(name = "HMT", value = "HMT")]
+    Hmt,
+}
+
+impl Region {
+    fn name(&self) -> &'static str {
+        match self {
+            Region::America => "America",
+            Region::Europe => "Europe",
+            Region::Asia => "Asia",
+            Region::Sea => "SEA",
+            Region::Hmt => "HMT",
+        }
+    }
+}
+
+/// One line summary of a member for thread welcomes.
+pub fn describe_member(user: Id<UserMarker>, role: &str, profile: Option<&Profile>) -> String {
+    match profile {
+        Some(profile) => format!("<@{}> ({}) - **{}** | UID `{}` | WL `{}` | {}",
+                                 user.get(), role, profile.name, profile.uid, profile.world_level, profile.region),
+        None => format!("<@{}> ({}) - no profile set, use `/profile set`", user.get(), role),
+    }
+}
+
+fn error_embed(description: &str) -> Embed {
+    EmbedBuilder::new()
+        .color(0xEE4B2B)
+        .title("Error")
+        .description(description)
+        .build()
+}
+
+impl ProfileCommand {
+    pub async fn handle(
+        interaction: Interaction,
+        data: CommandData,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let command = ProfileCommand::from_interaction(data.into())?;
+
+        let embed = match command {
+            ProfileCommand::Set(set) => {
+                if !set.uid.chars().all(|i| i.is_ascii_digit()) {
+                    error_embed("A UID should only contain digits.")
+                } else {
+                    let profile = Profile {
+                        uid: set.uid,
+                        name: set.name,
+                        world_level: set.world_level as i32,
+                        region: set.region.name().to_owned(),
+                    };
+
+                    bot.storage.set_profile(author, &profile).await?;
+
+                    EmbedBuilder::new()
+                        .color(0x50C878)
+                        .title("Success")
+                        .description("Saved your profile. It will be shown to your group in new threads.")
+                        .build()
+                }
+            },
+            ProfileCommand::View(view) => {
+                let user = view.user.unwrap_or(author);
+
+                match bot.storage.get_profile(user).await? {
+                    Some(profile) => EmbedBuilder::new()
+                        .color(0x63c5da)
+                        .title("Profile")
+                        .description(format!("<@{}>", user.get()))
+                        .field(EmbedFieldBuilder::new("Name", profile.name).inline())
+                        .field(EmbedFieldBuilder::new("UID", profile.uid).inline())
+                        .field(EmbedFieldBuilder::new("World Level", profile.world_level.to_string()).inline())
+                        .field(EmbedFieldBuilder::new("Region", profile.region).inline())
+                        .build(),
+                    None => error_embed("No profile has been set. Use `/profile set` to create one."),
+                }
+            },
+        };
+
+        let data = InteractionResponseDataBuilder::new()
+            .embeds([embed])
+            .flags(MessageFlags::EPHEMERAL)
+            .build();
+
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(data),
+        };
+
+        client.create_response(interaction.id, &interaction.token, &response).await?;
+
+        Ok(())
+    }
+}
+
+impl SlashCommand for ProfileCommand {
+    const NAME: &'static str = NAME;
+
+    fn handle(interaction: Interaction, data: CommandData, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(Self::handle(interaction, data, bot))
+    }
+}
@@ -3,17 +3,20 @@ use twilight_model::{
         ActionRow,
         Button,
         ButtonStyle,
-        Component
+        Component,
+        ComponentType,
     },
     id::{
         Id,
         marker::{
+            ChannelMarker,
+            MessageMarker,
             UserMarker,
         }
     },
-    application::interaction::{message_component::MessageComponentInteractionData, Interaction},
+    application::interaction::Interaction,
     http::interaction::{InteractionResponse, InteractionResponseType},
-    channel::{ChannelType::{GuildText, PrivateThread},
+    channel::{Channel, ChannelType::PrivateThread,
                 message::{MessageFlags, embed::Embed},
     },
 };
@@ -22,53 +25,70 @@ use twilight_util::builder::{
     embed::EmbedBuilder,
 };
 
+use std::time::{Duration, Instant};
+
 use rand::distributions::{Alphanumeric, DistString};
-use std::collections::hash_map::Entry::{Occupied, Vacant};
 
-use crate::{Bot, CombinedQueues};
+use crate::{Bot, QueueEntry};
+use crate::matchmaking::{Event, Group, Role};
+use crate::custom_id::{CustomId, DecodeError};
+use crate::error::{BotError, Result};
+use crate::interactions::{board::BoardKind, profile::describe_member, targets::Targets};
+use crate::registry::{ComponentData, ComponentHandler, HandlerFuture};
 
 pub struct Queue;
 
-impl Queue {
-    pub fn get_action_row() -> Component {
-        let id = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Coop => "Co-op",
+            Role::Carry => "Carry",
+            Role::Assist => "Assist",
+        }
+    }
 
+    fn label(&self) -> &'static str {
+        match self {
+            Role::Coop => "Queue 1",
+            Role::Carry => "Queue 2",
+            Role::Assist => "Queue 3",
+        }
+    }
+
+    /// The reply to joining, given the user's position in the queue.
+    fn joined_embed(&self, position: usize, coop_size: usize) -> Embed {
+        let description = match self {
+            Role::Coop => format!("Successfully Joined Queue 1\nQueue size: `{}/{}`", position, coop_size),
+            Role::Carry | Role::Assist => format!("Successfully Joined {}\nYour position: `{}`", self.label(), position),
+        };
+
+        EmbedBuilder::new()
+            .color(0x50C878)
+            .title("Success")
+            .description(description)
+            .build()
+    }
+}
+
+impl Queue {
+    pub fn get_action_row(kind: BoardKind, disabled: bool) -> Component {
         Component::ActionRow ( ActionRow {
-            components: Vec::from([Component::Button(Button {
-                custom_id: Some(format!("QueueA-{}", id).to_owned()),
-                disabled: false,
-                emoji: None,
-                label: Some("Queue 1".to_owned()),
-                style: ButtonStyle::Primary,
-                url: None,
-            }),
-            Component::Button(Button {
-                custom_id: Some(format!("QueueB-{}", id).to_owned()),
-                disabled: false,
+            components: kind.roles().iter().map(|role| Component::Button(Button {
+                custom_id: Some(CustomId::Queue(*role).encode()),
+                disabled,
                 emoji: None,
-                label: Some("Queue 2".to_owned()),
+                label: Some(role.label().to_owned()),
                 style: ButtonStyle::Primary,
                 url: None,
-            }),
-            Component::Button(Button {
-                custom_id: Some(format!("QueueC-{}", id).to_owned()),
-                disabled: false,
-                emoji: None,
-                label: Some("Queue 3".to_owned()),
-                style: ButtonStyle::Primary,
-                url: None,
-            }),
-            ]),
+            })).collect(),
         })
     }
 
-    fn get_cancel_button(disabled: bool) -> Component {
-        let id = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
-
+    pub fn get_cancel_button(board: Id<MessageMarker>, disabled: bool) -> Component {
         Component::ActionRow ( ActionRow {
             components: Vec::from([
                 Component::Button( Button {
-                    custom_id: Some(format!("Cancel-{}", id).to_owned()),
+                    custom_id: Some(CustomId::Cancel(board).encode()),
                     disabled,
                     emoji: None,
                     label: Some("Leave".to_owned()),
@@ -79,96 +99,11 @@ impl Queue {
         })
     }
 
-    pub async fn handle_queue_a(
-        interaction: Interaction,
-        bot: &Bot,
-    ) -> anyhow::Result<()> {
-
-        fn get_group(queue: &mut CombinedQueues, author: Id<UserMarker>) -> (Option<Vec<Id<UserMarker>>>, Embed, bool) {
-            let queue = &mut queue.queue_a;
-            queue.push(author);
-            let len = queue.len();
-
-            let embed = EmbedBuilder::new()
-                .color(0x50C878)
-                .title("Success")
-                .description(format!("Successfully Joined Queue 1\nQueue size: `{}/3`", len).as_str())
-                .build();
-
-            if len >= 3 {
-                (Some(queue.split_off(len-3)), embed, false)
-            } else{
-                (None, embed, false)
-            }
-        }
-
-        Self::handle_queue_generic(interaction, bot, get_group).await
-    }
-
-    pub async fn handle_queue_b(
+    pub async fn handle_queue_generic(
         interaction: Interaction,
         bot: &Bot,
-    ) -> anyhow::Result<()> {
-
-        fn get_group(queue: &mut CombinedQueues, author: Id<UserMarker>) -> (Option<Vec<Id<UserMarker>>>, Embed, bool) {
-            let queue_b = &mut queue.queue_b;
-            let queue_c = &mut queue.queue_c;
-            queue_b.push(author);
-            let len_b = queue_b.len();
-            let len_c = queue_c.len();
-
-            let embed = EmbedBuilder::new()
-                .color(0x50C878)
-                .title("Success")
-                .description(format!("Successfully Joined Queue 2\nYour position: `{}`", len_b).as_str())
-                .build();
-
-            if len_b >= 2 && len_c >= 1 {
-                let mut group = queue_b.drain(..2).collect::<Vec<Id<UserMarker>>>();
-                group.push(queue_c.remove(0));
-                (Some(group), embed, true)
-            } else{
-                (None, embed, true)
-            }
-        }
-
-        Self::handle_queue_generic(interaction, bot, get_group).await
-    }
-
-    pub async fn handle_queue_c(
-        interaction: Interaction,
-        bot: &Bot,
-    ) -> anyhow::Result<()> {
-        fn get_group(queue: &mut CombinedQueues, author: Id<UserMarker>) -> (Option<Vec<Id<UserMarker>>>, Embed, bool) {
-            let queue_b = &mut queue.queue_b;
-            let queue_c = &mut queue.queue_c;
-            queue_c.push(author);
-            let len_b = queue_b.len();
-            let len_c = queue_c.len();
-
-            let embed = EmbedBuilder::new()
-                .color(0x50C878)
-                .title("Success")
-                .description(format!("Successfully Joined Queue 3\nYour position: `{}`", len_c).as_str())
-                .build();
-
-            if len_b >= 2 && len_c >= 1 {
-                let mut group = queue_b.drain(..2).collect::<Vec<Id<UserMarker>>>();
-                group.push(queue_c.remove(0));
-                (Some(group), embed, true)
-            } else{
-                (None, embed, true)
-            }
-        }
-
-        Self::handle_queue_generic(interaction, bot, get_group).await
-    }
-
-    async fn handle_queue_generic(
-        interaction: Interaction,
-        bot: &Bot,
-        f: fn(&mut CombinedQueues, Id<UserMarker>) -> (Option<Vec<Id<UserMarker>>>, Embed, bool)
-    ) -> anyhow::Result<()> {
+        role: Role,
+    ) -> Result<()> {
         let client = bot.client.interaction(interaction.application_id);
 
         let acknolewedge = InteractionResponse {
@@ -178,131 +113,260 @@ impl Queue {
                        .build()),
         };
 
+        let message_id = interaction.message.as_ref().ok_or(BotError::Missing("interaction message"))?.id;
+        let channel_id = interaction.channel.as_ref().ok_or(BotError::Missing("interaction channel"))?.id;
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
         client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
 
+        // Boards posted before boards were stored have no kind on record, so they are posted again.
+        if bot.storage.get_board_channel(message_id).await?.is_none() {
+            return Err(BotError::user("This queue board is out of date. Please ask a moderator to run `/setup` again."));
+        }
 
-        let (group, embed, components, queuetype) = { // scope to unlock after finish
-            let mut queues = bot.queues.lock().await;
-            let message_id = interaction.message.as_ref().unwrap().id;
-            let queue: &mut CombinedQueues = match queues.get_mut(&message_id) {
-                Some(queue) => queue,
-                None => {
-                    match queues.entry(message_id) {
-                        Occupied(_) => anyhow::bail!("shouldn't be possible"),
-                        Vacant(entry) => entry.insert(CombinedQueues {
-                            queue_a: Vec::with_capacity(3),
-                            queue_b: Vec::new(),
-                            queue_c: Vec::new(),
-                        })
-                    }
+        let (embed, components, groups) = Self::join(bot, message_id, author, role).await?;
 
-                }
-            }; // rust moment
-            let author = interaction.author_id().unwrap();
-            if queue.contains(&author) {
-                (None, EmbedBuilder::new()
-                                    .color(0xFFE4C4)
-                                    .title("Error")
-                                    .description("Already joined a queue, request ignored.")
-                                    .build(),
-                                    Some(Self::get_cancel_button(false)),
-                                    None)
-            }
-            else if bot.is_thread(author).await?.unwrap() {
-                (None, EmbedBuilder::new()
-                                    .color(0xFFE4C4)
-                                    .title("Error")
-                                    .description("You are currently in a thread")
-                                    .build(), None, None)
-            }
-            else {
-                let group = f(queue, author);
-                (group.0, group.1, Some(Self::get_cancel_button(false)), Some(group.2))
-            }
-        };
+        client.create_followup(&interaction.token).embeds(&[embed])?.components(&components)?.await?;
 
-        if components.is_some() {
-            client.create_followup(&interaction.token).embeds(&[embed])?.components(&[components.unwrap()])?.await?;
-        }
-        else {
-            client.create_followup(&interaction.token).embeds(&[embed])?.await?;
-        }
+        Self::open_sessions(bot, channel_id, message_id, groups).await;
 
-        if group.is_none() {
-            return Ok(())
+        Ok(())
+    }
+
+    /// Adds `author` to a queue on the board, returning the reply and any groups that were formed.
+    pub async fn join(
+        bot: &Bot,
+        message_id: Id<MessageMarker>,
+        author: Id<UserMarker>,
+        role: Role,
+    ) -> Result<(Embed, Vec<Component>, Vec<Group<Id<UserMarker>>>)> {
+        let profile = bot.storage.get_profile(author).await?;
+
+        if role == Role::Carry && profile.is_none() {
+            return Ok((EmbedBuilder::new()
+                    .color(0xFFE4C4)
+                    .title("Profile required")
+                    .description("Please set your World Level with `/profile set` before joining Queue 2, so you are only matched with worlds you can help in.")
+                    .build(),
+                Vec::new(),
+                Vec::new()));
         }
 
-        let group = group.unwrap();
+        let entry = QueueEntry::new(author, profile.map(|i| i.world_level));
+        let kind = bot.storage.get_board_kind(message_id).await?;
+        // Looked up before taking the board's lock so a slow database never holds up other clicks.
+        // Joining and forming groups stay atomic, as `contains` and `apply` run under the lock below.
+        let in_thread = bot.storage.in_thread(author).await?;
+
+        let board = bot.board_or_insert(message_id, kind, false).await;
+        let mut queue = board.lock().await;
+        if !queue.kind.roles().contains(&role) {
+            Ok((EmbedBuilder::new()
+                    .color(0xEE4B2B)
+                    .title("Error")
+                    .description(format!("This board does not have a {} queue.", role.name()))
+                    .build(),
+                Vec::new(),
+                Vec::new()))
+        }
+        else if queue.paused {
+            Ok((EmbedBuilder::new()
+                    .color(0xFFE4C4)
+                    .title("Error")
+                    .description("This queue is paused for maintenance. Please try again later.")
+                    .build(),
+                Vec::new(),
+                Vec::new()))
+        }
+        else if queue.queues.contains(&author) {
+            Ok((EmbedBuilder::new()
+                    .color(0xFFE4C4)
+                    .title("Error")
+                    .description("Already joined a queue, request ignored.")
+                    .build(),
+                vec![Self::get_cancel_button(message_id, false), Targets::get_select_row(message_id, queue.kind)],
+                Vec::new()))
+        }
+        else if in_thread {
+            Ok((EmbedBuilder::new()
+                    .color(0xFFE4C4)
+                    .title("Error")
+                    .description("You are currently in a thread")
+                    .build(),
+                Vec::new(),
+                Vec::new()))
+        }
+        else {
+            let position = queue.queues.queue(role).len() + 1;
+            let groups = queue.apply(Event::Join(role, entry));
+            let embed = role.joined_embed(position, queue.kind.coop_size());
+
+            // Targets only matter while waiting, so skip the menu if a group formed straight away. A
+            // co-op user who picks targets soon enough is held for others sharing them.
+            let components = match queue.queues.contains(&author) {
+                true => vec![Self::get_cancel_button(message_id, false), Targets::get_select_row(message_id, queue.kind)],
+                false => vec![Self::get_cancel_button(message_id, false)],
+            };
+
+            Ok((embed, components, groups))
+        }
+    }
 
+    /// Opens a private thread under the board channel for a formed group and records the session.
+    ///
+    /// If anything fails once the thread exists, the thread is deleted and forgotten again, so an
+    /// error always means the group has no session.
+    pub async fn create_session(
+        bot: &Bot,
+        channel_id: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        queuetype: bool,
+        group: Vec<QueueEntry>,
+    ) -> Result<()> {
         let id = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+        let kind = bot.storage.get_board_kind(message_id).await?;
 
         let title = match queuetype {
-            Some(false) => format!("Echos Farming Thread - A{}", id),
-            Some(true) => format!("Echos Farming Thread - B{}", id),
-            _ => anyhow::bail!("got impossiblbe type"),
+            false => format!("{} - A{}", kind.thread_name(), id),
+            true => format!("{} - B{}", kind.thread_name(), id),
         };
 
         let thread = bot.client
-                        .create_thread(interaction.channel.unwrap().id, title.as_str(), PrivateThread)?
+                        .create_thread(channel_id, title.as_str(), PrivateThread)?
                         .invitable(false)
                         .await?
                         .model()
                         .await?;
 
+        if let Err(error) = Self::welcome(bot, thread.id, message_id, kind, queuetype, group).await {
+            if let Err(error) = bot.storage.remove_thread(thread.id).await {
+                tracing::warn!(?error, thread = thread.id.get(), "could not forget a failed session thread");
+            }
+
+            if let Err(error) = bot.client.delete_channel(thread.id).await {
+                tracing::warn!(?error, thread = thread.id.get(), "could not delete a failed session thread");
+            }
+
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    /// Records a new session thread and pings its members into it.
+    async fn welcome(
+        bot: &Bot,
+        thread: Id<ChannelMarker>,
+        message_id: Id<MessageMarker>,
+        kind: BoardKind,
+        queuetype: bool,
+        group: Vec<QueueEntry>,
+    ) -> Result<()> {
+        let mut members = Vec::with_capacity(group.len());
+        for (slot, entry) in group.iter().enumerate() {
+            let profile = bot.storage.get_profile(entry.user).await?;
+            let mut member = describe_member(entry.user, Role::for_slot(queuetype, slot).name(), profile.as_ref());
+            if !entry.targets.is_empty() {
+                member.push_str(&format!("\n> Targets: {}", entry.targets.join(", ")));
+            }
+            members.push(member);
+        }
+
+        let group = group.into_iter().map(|i| i.user).collect::<Vec<Id<UserMarker>>>();
+
+        // Tracked before anyone is pinged, so members never end up in a thread the bot has forgotten.
+        bot.storage.insert_thread(thread, message_id, &group).await?;
+
         let thread_embed = EmbedBuilder::new()
             .color(0x63c5da)
             .title("Welcome")
-            .description("Welcome to this echos farming thread. When you have finished, please use the command /end")
+            .description(format!("Welcome to this {}. When you have finished, please use the command /end\n\n{}", kind.thread_name().to_lowercase(), members.join("\n")))
             .build();
 
         let _ = bot.client
-            .create_message(thread.id)
+            .create_message(thread)
             .embeds(&[thread_embed])?
-            .content(format!("<@{}> <@{}> <@{}>", group[0].get(), group[1].get(), group[2].get()).as_str())?
+            .content(group.iter().map(|i| format!("<@{}>", i.get())).collect::<Vec<String>>().join(" ").as_str())?
             .await?
             .model()
             .await?;
 
-        let _ = bot.insert_thread(thread.id, group[0], group[1], group[2]).await;
+        Ok(())
+    }
+
+    /// Opens a session for each group formed on a board. A group whose session can't be opened is
+    /// put back at the head of its queues, and the other groups still get theirs.
+    pub async fn open_sessions(bot: &Bot, channel_id: Id<ChannelMarker>, message_id: Id<MessageMarker>, groups: Vec<Group<Id<UserMarker>>>) {
+        for group in groups {
+            let Err(error) = Self::create_session(bot, channel_id, message_id, group.queue_type, group.members.clone()).await else {
+                continue;
+            };
+
+            tracing::error!(?error, board = message_id.get(), "failed to open a session, requeueing its members");
+
+            if let Some(queue) = bot.board(message_id).await {
+                let mut queue = queue.lock().await;
+                for (slot, entry) in group.members.into_iter().enumerate() {
+                    queue.queues.restore(Role::for_slot(group.queue_type, slot), entry);
+                }
+            }
+        }
+    }
+
+    /// Opens sessions for groups formed on a board by something other than a join, such as a
+    /// leave or a hold running out.
+    pub async fn start_sessions(bot: &Bot, message_id: Id<MessageMarker>, groups: Vec<Group<Id<UserMarker>>>) -> Result<()> {
+        if groups.is_empty() {
+            return Ok(());
+        }
+
+        let channel_id = bot.storage.get_board_channel(message_id).await?.ok_or(BotError::Missing("board channel"))?;
+        Self::open_sessions(bot, channel_id, message_id, groups).await;
 
         Ok(())
     }
 
+    /// Reads the queue type back out of a thread name created by `create_session`.
+    pub fn thread_queue_type(channel: &Channel) -> Option<bool> {
+        let name = channel.name.as_ref()?;
+        match name.rsplit(" - ").next()?.chars().next() {
+            Some('A') => Some(false),
+            Some('B') => Some(true),
+            _ => None,
+        }
+    }
+
     pub async fn handle_cancel(
         interaction: Interaction,
         bot: &Bot,
-    ) -> anyhow::Result<()> {
-        let message = interaction.message.clone().unwrap();
-        let reference = message.clone().reference.unwrap().message_id.unwrap();
-        let embed = {
-            let mut queues = bot.queues.lock().await;
-            match queues.get_mut(&reference) {
-                Some(queue) => {
-                    let author = interaction.author_id().unwrap();
-                    if queue.contains(&author) {
-                        queue.pop(&author);
-                        EmbedBuilder::new()
-                            .color(0x50C878)
-                            .title("Confirmed")
-                            .description("Leaving queue.")
-                            .build()
-                    }
-                    else {
-                        EmbedBuilder::new()
-                            .color(0xEE4B2B)
-                            .title("Error")
-                            .description("Attempted to leave queue when not in one.")
-                            .build()
-                    }
-                },
-                None => {
-                    EmbedBuilder::new()
-                        .color(0xEE4B2B)
-                        .title("Error")
-                        .description("Attempted to leave queue when not in one.")
-                        .build()
-                },
-            }
+        reference: Id<MessageMarker>,
+    ) -> Result<()> {
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let (left, groups) = match bot.board(reference).await {
+            Some(queue) => {
+                let mut queue = queue.lock().await;
+                match queue.queues.contains(&author) {
+                    true => (true, queue.apply(Event::Leave(author))),
+                    false => (false, Vec::new()),
+                }
+            },
+            None => (false, Vec::new()),
+        };
+
+        let embed = if left {
+            EmbedBuilder::new()
+                .color(0x50C878)
+                .title("Confirmed")
+                .description("Leaving queue.")
+                .build()
+        }
+        else {
+            EmbedBuilder::new()
+                .color(0xEE4B2B)
+                .title("Error")
+                .description("Attempted to leave queue when not in one.")
+                .build()
         };
 
         let client = bot.client.interaction(interaction.application_id);
@@ -319,6 +383,47 @@ impl Queue {
 
         client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
 
-        Ok(())
+        Self::start_sessions(bot, reference, groups).await
+    }
+}
+
+/// How often waiting co-op users are checked for holds that ran out.
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
+impl Bot {
+    /// Forms the groups held back for shared targets once their hold runs out, until the task is dropped.
+    pub async fn run_queue_ticks(&self) {
+        loop {
+            tokio::time::sleep(TICK_INTERVAL).await;
+
+            for (message_id, queue) in self.boards().await {
+                let groups = {
+                    let mut queue = queue.lock().await;
+                    if queue.paused {
+                        continue;
+                    }
+
+                    queue.apply(Event::Tick(Instant::now()))
+                };
+
+                if let Err(error) = Queue::start_sessions(self, message_id, groups).await {
+                    tracing::error!(?error, board = message_id.get(), "failed to start held sessions");
+                }
+            }
+        }
+    }
+}
+
+impl ComponentHandler for Queue {
+    const TAGS: &'static [&'static str] = &["queue", "cancel"];
+
+    fn handle(interaction: Interaction, data: ComponentData, id: CustomId, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(async move {
+            match (data.component_type(), id) {
+                (Some(ComponentType::Button), CustomId::Queue(role)) => Self::handle_queue_generic(interaction, bot, role).await,
+                (Some(ComponentType::Button), CustomId::Cancel(board)) => Self::handle_cancel(interaction, bot, board).await,
+                _ => Err(DecodeError::Malformed.into()),
+            }
+        })
     }
 }
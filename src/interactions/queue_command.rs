@@ -0,0 +1,518 @@
+use std::time::Duration;
+
+use twilight_interactions::command::{CommandModel, CommandOption, CreateCommand, CreateOption};
+use twilight_model::{
+    application::interaction::{application_command::CommandData, Interaction},
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    channel::message::{MessageFlags, embed::Embed},
+    id::{
+        Id,
+        marker::{
+            ChannelMarker,
+            MessageMarker,
+            UserMarker,
+        }
+    },
+};
+use twilight_util::builder::{
+    InteractionResponseDataBuilder,
+    embed::EmbedBuilder,
+};
+
+use crate::{Bot, QueueEntry};
+use crate::error::{BotError, Result};
+use crate::interactions::queue::Queue;
+use crate::matchmaking::{Event, Role};
+use crate::registry::{HandlerFuture, SlashCommand};
+
+pub const NAME: &str = "queue";
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "queue", desc = "join, leave or check the queues")]
+pub enum QueueCommand {
+    #[command(name = "join")]
+    Join(QueueJoin),
+    #[command(name = "leave")]
+    Leave(QueueLeave),
+    #[command(name = "status")]
+    Status(QueueStatus),
+    #[command(name = "admin")]
+    Admin(QueueAdmin),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "join", desc = "join a queue")]
+pub struct QueueJoin {
+    /// Which queue to join
+    role: QueueRole,
+    /// Channel of the queue board, defaults to the server's latest board
+    #[command(channel_types = "guild_text")]
+    board: Option<Id<ChannelMarker>>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "leave", desc = "leave every queue you are in")]
+pub struct QueueLeave;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "status", desc = "show which queue you are in")]
+pub struct QueueStatus;
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "admin", desc = "manage the queues of a board")]
+pub enum QueueAdmin {
+    #[command(name = "list")]
+    List(AdminList),
+    #[command(name = "kick")]
+    Kick(AdminKick),
+    #[command(name = "clear")]
+    Clear(AdminClear),
+    #[command(name = "force")]
+    Force(AdminForce),
+    #[command(name = "pause")]
+    Pause(AdminPause),
+    #[command(name = "resume")]
+    Resume(AdminResume),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "list", desc = "list everyone waiting on a board")]
+pub struct AdminList {
+    /// Channel of the queue board, defaults to the server's latest board
+    #[command(channel_types = "guild_text")]
+    board: Option<Id<ChannelMarker>>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "kick", desc = "remove a user from the queues of a board")]
+pub struct AdminKick {
+    /// User to remove
+    user: Id<UserMarker>,
+    /// Channel of the queue board, defaults to the server's latest board
+    #[command(channel_types = "guild_text")]
+    board: Option<Id<ChannelMarker>>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "clear", desc = "remove everyone from the queues of a board")]
+pub struct AdminClear {
+    /// Channel of the queue board, defaults to the server's latest board
+    #[command(channel_types = "guild_text")]
+    board: Option<Id<ChannelMarker>>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "force", desc = "put users into a session thread")]
+pub struct AdminForce {
+    /// Kind of session to create
+    kind: SessionKind,
+    /// First member, a carry for carry sessions
+    user1: Id<UserMarker>,
+    /// Second member, a carry for carry sessions
+    user2: Id<UserMarker>,
+    /// Third member, the assist for carry sessions. Not needed for free co-op boards
+    user3: Option<Id<UserMarker>>,
+    /// Channel of the queue board, defaults to the server's latest board
+    #[command(channel_types = "guild_text")]
+    board: Option<Id<ChannelMarker>>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "pause", desc = "disable a board during maintenance")]
+pub struct AdminPause {
+    /// Channel of the queue board, defaults to the server's latest board
+    #[command(channel_types = "guild_text")]
+    board: Option<Id<ChannelMarker>>,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "resume", desc = "enable a paused board")]
+pub struct AdminResume {
+    /// Channel of the queue board, defaults to the server's latest board
+    #[command(channel_types = "guild_text")]
+    board: Option<Id<ChannelMarker>>,
+}
+
+#[derive(CommandOption, CreateOption)]
+pub enum SessionKind {
+    #[option(name = "Co-op", value = "coop")]
+    Coop,
+    #[option(name = "Carry", value = "carry")]
+    Carry,
+}
+
+#[derive(CommandOption, CreateOption)]
+pub enum QueueRole {
+    #[option(name = "Co-op (1)", value = "coop")]
+    Coop,
+    #[option(name = "Carry (2)", value = "carry")]
+    Carry,
+    #[option(name = "Assist (3)", value = "assist")]
+    Assist,
+}
+
+impl QueueRole {
+    fn role(&self) -> Role {
+        match self {
+            QueueRole::Coop => Role::Coop,
+            QueueRole::Carry => Role::Carry,
+            QueueRole::Assist => Role::Assist,
+        }
+    }
+}
+
+fn format_wait(wait: Duration) -> String {
+    let secs = wait.as_secs();
+    match secs {
+        3600.. => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        60.. => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}s", secs),
+    }
+}
+
+fn error_embed(description: &str) -> Embed {
+    EmbedBuilder::new()
+        .color(0xEE4B2B)
+        .title("Error")
+        .description(description)
+        .build()
+}
+
+impl QueueCommand {
+    pub async fn handle(
+        interaction: Interaction,
+        data: CommandData,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+
+        let acknolewedge = InteractionResponse {
+            kind: InteractionResponseType::DeferredChannelMessageWithSource,
+            data: Some(InteractionResponseDataBuilder::new()
+                       .flags(MessageFlags::EPHEMERAL)
+                       .build()),
+        };
+
+        client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
+
+        let command = QueueCommand::from_interaction(data.into())?;
+
+        match command {
+            QueueCommand::Join(join) => Self::handle_join(interaction, join, bot).await,
+            QueueCommand::Leave(_) => Self::handle_leave(interaction, bot).await,
+            QueueCommand::Status(_) => Self::handle_status(interaction, bot).await,
+            QueueCommand::Admin(admin) => Self::handle_admin(interaction, admin, bot).await,
+        }
+    }
+
+    /// Finds the board a command refers to, falling back to the server's latest board. In DMs,
+    /// falls back to the board the user is waiting on or their last session came from instead.
+    async fn resolve_board(
+        interaction: &Interaction,
+        board: Option<Id<ChannelMarker>>,
+        bot: &Bot,
+    ) -> Result<Option<(Id<MessageMarker>, Id<ChannelMarker>)>> {
+        if let Some(channel) = board {
+            return bot.storage.get_channel_board(channel).await;
+        }
+
+        if let Some(guild) = interaction.guild_id {
+            return bot.storage.get_default_board(guild).await;
+        }
+
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        for (board, queue) in bot.boards().await {
+            if queue.lock().await.queues.contains(&author) {
+                return Ok(bot.storage.get_board_channel(board).await?.map(|channel| (board, channel)));
+            }
+        }
+
+        let requeues = bot.requeues.lock().await;
+        Ok(requeues.values()
+            .filter(|i| i.users.contains(&Some(author)))
+            .max_by_key(|i| i.created)
+            .map(|i| (i.board, i.channel)))
+    }
+
+    fn board_not_found(interaction: &Interaction) -> Embed {
+        match interaction.guild_id {
+            Some(_) => error_embed("Could not find a queue board. Please pick the channel of a board with the `board` option."),
+            None => error_embed("Could not tell which board you mean. Please use this command in a server."),
+        }
+    }
+
+    async fn handle_join(
+        interaction: Interaction,
+        join: QueueJoin,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let Some((board, channel)) = Self::resolve_board(&interaction, join.board, bot).await? else {
+            let embed = Self::board_not_found(&interaction);
+            client.create_followup(&interaction.token).embeds(&[embed])?.await?;
+
+            return Ok(());
+        };
+
+        let (embed, components, groups) = Queue::join(bot, board, author, join.role.role()).await?;
+
+        client.create_followup(&interaction.token).embeds(&[embed])?.components(&components)?.await?;
+
+        Queue::open_sessions(bot, channel, board, groups).await;
+
+        Ok(())
+    }
+
+    async fn handle_leave(
+        interaction: Interaction,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let mut left = Vec::new();
+        for (board, queue) in bot.boards().await {
+            let mut queue = queue.lock().await;
+            if queue.queues.contains(&author) {
+                left.push((board, queue.apply(Event::Leave(author))));
+            }
+        }
+
+        let embed = if !left.is_empty() {
+            EmbedBuilder::new()
+                .color(0x50C878)
+                .title("Confirmed")
+                .description("Leaving queue.")
+                .build()
+        } else {
+            error_embed("Attempted to leave queue when not in one.")
+        };
+
+        client.create_followup(&interaction.token).embeds(&[embed])?.await?;
+
+        for (board, groups) in left {
+            Queue::start_sessions(bot, board, groups).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_status(
+        interaction: Interaction,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let mut lines = Vec::new();
+
+        for (_, queue) in bot.boards().await {
+            let queue = queue.lock().await;
+            for (name, queue) in [("Queue 1", &queue.queues.queue_a), ("Queue 2", &queue.queues.queue_b), ("Queue 3", &queue.queues.queue_c)] {
+                if let Some(position) = queue.iter().position(|i| i.user == author) {
+                    lines.push(format!("**{}**: position `{}` of `{}`", name, position + 1, queue.len()));
+                }
+            }
+        }
+
+        if bot.storage.in_thread(author).await? {
+            lines.push("You are currently in a thread.".to_owned());
+        }
+
+        if lines.is_empty() {
+            lines.push("You are not in any queue.".to_owned());
+        }
+
+        let embed = EmbedBuilder::new()
+            .color(0x63c5da)
+            .title("Queue status")
+            .description(lines.join("\n"))
+            .build();
+
+        client.create_followup(&interaction.token).embeds(&[embed])?.await?;
+
+        Ok(())
+    }
+
+    async fn handle_admin(
+        interaction: Interaction,
+        admin: QueueAdmin,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+
+        if !bot.is_moderator(&interaction).await? {
+            return bot.deny(&interaction, true).await;
+        }
+
+        let board = match &admin {
+            QueueAdmin::List(list) => list.board,
+            QueueAdmin::Kick(kick) => kick.board,
+            QueueAdmin::Clear(clear) => clear.board,
+            QueueAdmin::Force(force) => force.board,
+            QueueAdmin::Pause(pause) => pause.board,
+            QueueAdmin::Resume(resume) => resume.board,
+        };
+
+        let Some((board, channel)) = Self::resolve_board(&interaction, board, bot).await? else {
+            let embed = Self::board_not_found(&interaction);
+            client.create_followup(&interaction.token).embeds(&[embed])?.await?;
+
+            return Ok(());
+        };
+
+        let embed = match admin {
+            QueueAdmin::List(_) => {
+                let (description, paused) = match bot.board(board).await {
+                    Some(queue) => {
+                        let queue = queue.lock().await;
+                        let description = [("Queue 1", &queue.queues.queue_a), ("Queue 2", &queue.queues.queue_b), ("Queue 3", &queue.queues.queue_c)]
+                            .iter()
+                            .map(|(name, queue)| {
+                                let members = queue.iter()
+                                    .map(|i| format!("<@{}> - waiting `{}`", i.user.get(), format_wait(i.joined.elapsed())))
+                                    .collect::<Vec<String>>();
+                                let members = if members.is_empty() { "Empty".to_owned() } else { members.join("\n") };
+                                format!("**{}** (`{}`)\n{}", name, queue.len(), members)
+                            })
+                            .collect::<Vec<String>>()
+                            .join("\n\n");
+
+                        (description, queue.paused)
+                    },
+                    None => ("No one has queued on this board yet.".to_owned(), false),
+                };
+
+                EmbedBuilder::new()
+                    .color(0x63c5da)
+                    .title(if paused { "Queues (paused)" } else { "Queues" })
+                    .description(description)
+                    .build()
+            },
+            QueueAdmin::Kick(kick) => {
+                let (removed, groups) = match bot.board(board).await {
+                    Some(queue) => {
+                        let mut queue = queue.lock().await;
+                        match queue.queues.contains(&kick.user) {
+                            true => (true, queue.apply(Event::Leave(kick.user))),
+                            false => (false, Vec::new()),
+                        }
+                    },
+                    None => (false, Vec::new()),
+                };
+
+                // Whoever the kicked user was holding up can be grouped now.
+                Queue::open_sessions(bot, channel, board, groups).await;
+
+                if removed {
+                    EmbedBuilder::new()
+                        .color(0x50C878)
+                        .title("Success")
+                        .description(format!("Removed <@{}> from the queue.", kick.user.get()))
+                        .build()
+                } else {
+                    error_embed("That user is not in a queue on this board.")
+                }
+            },
+            QueueAdmin::Clear(_) => {
+                let removed = match bot.board(board).await {
+                    Some(queue) => queue.lock().await.queues.clear(),
+                    None => 0,
+                };
+
+                EmbedBuilder::new()
+                    .color(0x50C878)
+                    .title("Success")
+                    .description(format!("Cleared the board, removing `{}` users.", removed))
+                    .build()
+            },
+            QueueAdmin::Force(force) => {
+                let kind = bot.storage.get_board_kind(board).await?;
+                let (role, size) = match force.kind {
+                    SessionKind::Coop => (Role::Coop, kind.coop_size()),
+                    SessionKind::Carry => (Role::Carry, 3),
+                };
+
+                let group = [Some(force.user1), Some(force.user2), force.user3].into_iter()
+                    .flatten()
+                    .collect::<Vec<Id<UserMarker>>>();
+
+                if !kind.roles().contains(&role) {
+                    error_embed(&format!("This board does not have {} sessions.", role.name()))
+                } else if group.len() != size {
+                    error_embed(&format!("A session on this board needs {} users.", size))
+                } else if group.iter().enumerate().any(|(index, i)| group[..index].contains(i)) {
+                    error_embed("A session needs different users.")
+                } else {
+                    let mut busy = Vec::new();
+                    for user in &group {
+                        if bot.storage.in_thread(*user).await? {
+                            busy.push(format!("<@{}>", user.get()));
+                        }
+                    }
+
+                    if !busy.is_empty() {
+                        error_embed(&format!("Already in a thread: {}", busy.join(" ")))
+                    } else {
+                        for (_, queue) in bot.boards().await {
+                            let mut queue = queue.lock().await;
+                            for user in &group {
+                                queue.queues.pop(user);
+                            }
+                        }
+
+                        let queuetype = matches!(force.kind, SessionKind::Carry);
+                        let group = group.into_iter().map(|i| QueueEntry::new(i, None)).collect();
+                        Queue::create_session(bot, channel, board, queuetype, group).await?;
+
+                        EmbedBuilder::new()
+                            .color(0x50C878)
+                            .title("Success")
+                            .description("Created a session thread for the selected users.")
+                            .build()
+                    }
+                }
+            },
+            QueueAdmin::Pause(_) => Self::set_paused(bot, board, channel, true).await?,
+            QueueAdmin::Resume(_) => Self::set_paused(bot, board, channel, false).await?,
+        };
+
+        client.create_followup(&interaction.token).embeds(&[embed])?.await?;
+
+        Ok(())
+    }
+
+    async fn set_paused(
+        bot: &Bot,
+        board: Id<MessageMarker>,
+        channel: Id<ChannelMarker>,
+        paused: bool,
+    ) -> Result<Embed> {
+        let kind = bot.storage.get_board_kind(board).await?;
+
+        bot.board_or_insert(board, kind, paused).await.lock().await.paused = paused;
+
+        bot.storage.set_board_paused(board, paused).await?;
+
+        bot.client.update_message(channel, board)
+            .components(Some(&[Queue::get_action_row(kind, paused)]))?
+            .await?;
+
+        Ok(EmbedBuilder::new()
+            .color(0x50C878)
+            .title("Success")
+            .description(if paused { "Paused the board." } else { "Resumed the board." })
+            .build())
+    }
+}
+
+impl SlashCommand for QueueCommand {
+    const NAME: &'static str = NAME;
+
+    fn handle(interaction: Interaction, data: CommandData, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(Self::handle(interaction, data, bot))
+    }
+}
@@ -1,16 +1,15 @@
-use std::{mem, sync::Arc};
-
-use anyhow::{bail, Context};
 use twilight_model::{
-    channel::{Channel, message::component::{ActionRow, Component, SelectMenu, SelectMenuOption, TextInput, TextInputStyle}, message::{MessageFlags, embed::Embed}},
+    channel::{Channel, message::component::{ActionRow, Component, ComponentType, SelectMenu, SelectMenuOption, TextInput, TextInputStyle}, message::{MessageFlags, embed::Embed}},
     id::{
         Id,
         marker::{
+            ChannelMarker,
+            MessageMarker,
             UserMarker,
         }
     },
     user::User,
-    application::interaction::{message_component::MessageComponentInteractionData, Interaction, modal::ModalInteractionData},
+    application::interaction::{Interaction, modal::ModalInteractionData},
     http::interaction::{InteractionResponse, InteractionResponseType},
 };
 
@@ -21,10 +20,14 @@ use twilight_util::builder::{
 use rand::distributions::{Alphanumeric, DistString};
 
 use crate::Bot;
-
-fn get_select_row(users: Vec<User>) -> Component {
-    let id = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
-
+use crate::error::{BotError, Result};
+use crate::custom_id::{CustomId, DecodeError};
+use crate::storage::{ReportDraft, Session};
+use crate::interactions::{board::BoardKind, queue::Queue, requeue::Requeue};
+use crate::matchmaking::Role;
+use crate::registry::{ComponentData, ComponentHandler, HandlerFuture};
+
+fn get_select_row(token: &str, users: Vec<User>) -> Component {
     let mut options: Vec<SelectMenuOption> = Vec::new();
 
     for user in users {
@@ -41,9 +44,9 @@ fn get_select_row(users: Vec<User>) -> Component {
 
     Component::ActionRow(ActionRow {
         components: vec![Component::SelectMenu(SelectMenu {
-            custom_id: format!("Report - {}", id).to_owned(),
+            custom_id: CustomId::Report(token.to_owned()).encode(),
             disabled: false,
-            max_values: Some(2),
+            max_values: Some(options.len() as u8),
             min_values: Some(1),
             options,
             placeholder: Some("Choose a user".to_owned()),
@@ -52,11 +55,9 @@ fn get_select_row(users: Vec<User>) -> Component {
 }
 
 fn get_modal() -> Component {
-    let id = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
-
     Component::ActionRow(ActionRow {
         components: vec![Component::TextInput(TextInput {
-            custom_id: format!("TextInput - {}", id).to_owned(),
+            custom_id: CustomId::ReportReason.encode(),
             label: "Report reason for user(s)".to_owned(),
             required: Some(true),
             style: TextInputStyle::Paragraph,
@@ -68,83 +69,178 @@ fn get_modal() -> Component {
     })
 }
 
+/// The channel reports and reputation changes are logged to, from `LOG`.
+pub fn log_channel() -> Result<Id<ChannelMarker>> {
+    std::env::var("LOG").ok()
+        .and_then(|i| i.parse::<u64>().ok())
+        .and_then(Id::new_checked)
+        .ok_or(BotError::Config("LOG"))
+}
+
 impl Bot {
-    pub async fn handle_rep(&self, users: Vec<Id<UserMarker>>, channel: Channel) -> anyhow::Result<()> {
-        let name = channel.name.unwrap();
-        let index = name.find('-').unwrap();
-        let queue_type = match name.chars().nth(index + 2){
-            Some('A') => false,
-            Some('B') => true,
-            _ => anyhow::bail!("invalid queue type"),
+    /// Stops tracking a session thread and awards its members' rep, returning false if another
+    /// event already finished it.
+    pub async fn award_rep(&self, session: &Session, channel: &Channel) -> Result<bool> {
+        let queue_type = match Queue::thread_queue_type(channel) {
+            Some(queue_type) => queue_type,
+            None => return Err(BotError::Inconsistent("session thread name has no queue type")),
         };
 
-        if queue_type {
-            let _ = self.update_user(*users.get(2).unwrap(), 2).await;
+        let kind = match session.board {
+            Some(board) => self.storage.get_board_kind(board).await?,
+            None => BoardKind::Elite,
+        };
+
+        let rep = session.users.iter()
+            .enumerate()
+            .filter_map(|(slot, user)| user.map(|user| (user, kind.rep(Role::for_slot(queue_type, slot)))))
+            .filter(|(_, rep)| *rep != 0)
+            .collect::<Vec<(Id<UserMarker>, i32)>>();
+
+        self.storage.finish_thread(channel.id, &rep).await
+    }
+
+    /// Offers requeueing and sends every remaining member a poll to report the others.
+    pub async fn send_polls(&self, session: Session, channel: Channel) -> Result<()> {
+        let users = session.users;
+
+        let requeue = self.register_requeue(channel.id,
+                                            channel.parent_id,
+                                            session.board,
+                                            Queue::thread_queue_type(&channel),
+                                            users.clone()).await;
+
+        // A member who can't be fetched only misses out on their own poll and being listed in the others'.
+        let mut members: Vec<User> = Vec::new();
+        for id in users.iter().flatten() {
+            match self.fetch_user(*id).await {
+                Ok(user) => members.push(user),
+                Err(error) => tracing::warn!(?error, user = id.get(), "could not fetch a session member for the report poll"),
+            }
         }
-        else {
-            for user in &users {
-                let _ = self.update_user(*user, 1).await;
+
+        for member in &members {
+            let others = members.iter()
+                .filter(|i| i.id != member.id)
+                .cloned()
+                .collect::<Vec<User>>();
+
+            if let Err(error) = self.dm_poll(member.clone(), others, channel.id, requeue).await {
+                tracing::warn!(?error, user = member.id.get(), "could not send a report poll");
             }
         }
 
+        Ok(())
+    }
 
-        match users.as_slice() {
-            [id1, id2, id3] => {
-                let user1 = self.client.user(*id1).await?.model().await?;
-                let user2 = self.client.user(*id2).await?.model().await?;
-                let user3 = self.client.user(*id3).await?.model().await?;
+    async fn fetch_user(&self, id: Id<UserMarker>) -> Result<User> {
+        Ok(self.client.user(id).await?.model().await?)
+    }
 
-                let _ = self.dm_poll(user1.clone(), user2.clone(), user3.clone()).await;
-                let _ = self.dm_poll(user2.clone(), user1.clone(), user3.clone()).await;
-                let _ = self.dm_poll(user3.clone(), user1.clone(), user2.clone()).await;
-            },
-            _ => bail!("Not 3 users in users"),
-        };
+    async fn dm_poll(&self, user: User, others: Vec<User>, session: Id<ChannelMarker>, requeue: bool) -> Result<()> {
+        let mut components: Vec<Component> = Vec::new();
 
-        let _ = self.remove_thread(channel.id).await;
+        let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
 
-        Ok(())
-    }
+        if !others.is_empty() {
+            let remaining = others.iter().map(|i| i.id).collect::<Vec<Id<UserMarker>>>();
+            self.storage.insert_report_draft(&token, user.id, session, &remaining).await?;
+            components.push(get_select_row(&token, others));
+        }
 
+        if requeue {
+            components.push(Requeue::get_action_row(session, false));
+        }
+
+        if components.is_empty() {
+            return Ok(());
+        }
 
-    async fn dm_poll(&self, user1: User, user2: User, user3: User) -> anyhow::Result<()> {
-        let channel = self.client.create_private_channel(user1.id).await?.model().await?;
+        let channel = self.client.create_private_channel(user.id).await?.model().await?;
 
         let embed = EmbedBuilder::new()
             .color(0x50C878)
             .title("Thank you for your participation")
-            .description("Would you like to report any members? If so, please select them below. If not, you may ignore this message.")
+            .description("Would you like to report any members? If so, please select them below. If not, you may ignore this message.\n\nYou can also jump straight back into the queue with the buttons below.")
             .build();
 
-        let _ = self.client.create_message(channel.id)
+        let message = self.client.create_message(channel.id)
             .embeds(&[embed])?
-            .components(&[get_select_row(vec![user2, user3])])?
+            .components(&components)?
+            .await?
+            .model()
             .await?;
 
+        self.storage.set_report_draft_message(&token, channel.id, message.id).await?;
+
         Ok(())
     }
 
 }
 
+fn error_response(description: &str) -> InteractionResponse {
+    let embed = EmbedBuilder::new()
+        .color(0xEE4B2B)
+        .title("Error")
+        .description(description)
+        .build();
+
+    InteractionResponse {
+        kind: InteractionResponseType::ChannelMessageWithSource,
+        data: Some(InteractionResponseDataBuilder::new()
+                   .embeds([embed])
+                   .flags(MessageFlags::EPHEMERAL)
+                   .build()),
+    }
+}
+
+/// Loads the draft behind a report component, replying with an error if it can't be used.
+async fn load_draft(interaction: &Interaction, bot: &Bot, token: &str) -> Result<Option<ReportDraft>> {
+    let client = bot.client.interaction(interaction.application_id);
+    let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+    let description = match bot.storage.get_report_draft(token).await? {
+        Some(draft) if draft.reporter == author => return Ok(Some(draft)),
+        Some(_) => "This report poll belongs to someone else.",
+        None => "This report poll has already been used.",
+    };
+
+    client.create_response(interaction.id, &interaction.token, &error_response(description)).await?;
+
+    Ok(None)
+}
+
 pub async fn handle_report(
         interaction: Interaction,
         bot: &Bot,
+        token: &str,
         data: &[String]
-    ) -> anyhow::Result<()> {
-
-    let id = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+    ) -> Result<()> {
 
     let client = bot.client.interaction(interaction.application_id);
 
-    let mut string = data[0].clone();
-    if data.len() > 1 {
-        string.push_str(&(" - ".to_owned() + data[1].as_str()));
+    let Some(draft) = load_draft(&interaction, bot, token).await? else {
+        return Ok(());
+    };
+
+    // Only members still on the poll can be reported, whatever the menu sent.
+    let selected = draft.remaining.iter()
+        .filter(|i| data.contains(&i.to_string()))
+        .copied()
+        .collect::<Vec<Id<UserMarker>>>();
+
+    if selected.is_empty() {
+        client.create_response(interaction.id, &interaction.token, &error_response("Those members have already been reported.")).await?;
+
+        return Ok(());
     }
 
+    bot.storage.update_report_draft(token, &draft.remaining, &selected).await?;
+
     let data = InteractionResponseDataBuilder::new()
                    .components([get_modal()])
                    .title("Report")
-                   .custom_id(format!("Modal - {} - {}", string, id))
+                   .custom_id(CustomId::ReportModal(token.to_owned()).encode())
                    .build();
 
     let acknolewedge = InteractionResponse {
@@ -161,14 +257,40 @@ pub async fn handle_text (
     interaction: Interaction,
     data: ModalInteractionData,
     bot: &Bot,
-) -> anyhow::Result<()> {
-    let report = data.components.get(0).unwrap().components.get(0).unwrap().value.as_ref().unwrap();
+    token: &str,
+) -> Result<()> {
+    let report = data.components.first()
+        .and_then(|i| i.components.first())
+        .and_then(|i| i.value.as_ref())
+        .ok_or(BotError::Missing("report reason"))?;
 
-    let reporter = interaction.user.unwrap().id;
+    let client = bot.client.interaction(interaction.application_id);
 
-    let users =  data.custom_id.split(" - ").collect::<Vec<&str>>();
+    let Some(draft) = load_draft(&interaction, bot, token).await? else {
+        return Ok(());
+    };
 
-    let client = bot.client.interaction(interaction.application_id);
+    let reporter = draft.reporter;
+    let users = draft.selected;
+
+    if users.is_empty() {
+        client.create_response(interaction.id, &interaction.token, &error_response("Please choose who to report again.")).await?;
+
+        return Ok(());
+    }
+
+    let remaining = draft.remaining.iter()
+        .filter(|i| !users.contains(i))
+        .copied()
+        .collect::<Vec<Id<UserMarker>>>();
+
+    // A second submit of the same form finds the selection already claimed, and the penalty goes
+    // with the claim so a filed report always counts.
+    if !bot.storage.claim_report_draft(token, &users, &remaining, 1).await? {
+        client.create_response(interaction.id, &interaction.token, &error_response("This report has already been filed.")).await?;
+
+        return Ok(());
+    }
 
     let embed = EmbedBuilder::new()
         .color(0x50C878)
@@ -188,60 +310,85 @@ pub async fn handle_text (
 
     client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
 
-    let message = client.response(&interaction.token).await?.model().await?.reference.unwrap();
-
-    let message = bot.client.message(message.channel_id.unwrap(), message.message_id.unwrap()).await?.model().await?;
+    if remaining.is_empty() {
+        bot.storage.remove_report_draft(token).await?;
+    }
 
-    let mut options: Vec<SelectMenuOption> = if let Component::ActionRow (ActionRow { components: arr }) = message.components.get(0).unwrap() {
-        if let Component::SelectMenu ( SelectMenu { options: options, .. } ) = arr.get(0).unwrap() {
-            options.to_vec()
-        } else {
-            bail!("Should be a select menu, is not.")
+    // The report is already counted, so the poll and the log are only updated as well as they can be.
+    if let Some((channel_id, message_id)) = draft.message {
+        if let Err(error) = refresh_poll(&interaction, bot, token, &remaining, channel_id, message_id).await {
+            tracing::warn!(?error, "could not update report poll");
         }
-    } else {
-        bail!("First component should be Action row.")
-    };
-
-    options.retain(|i| !users.contains(&i.value.as_str()));
-
-
-    if options.len() > 0 {
-        let id = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
-        let _ = bot.client.update_message(message.channel_id, message.id)
-                    .components(Some(&[Component::ActionRow(ActionRow {
-            components: vec![Component::SelectMenu(SelectMenu {
-                custom_id: format!("Report - {}", id).to_owned(),
-                disabled: false,
-                max_values: None,
-                min_values: None,
-                options,
-                placeholder: Some("Choose a user".to_owned()),
-            })],})]))?.await?;
-    }
-    else {
-        let message = bot.client.update_message(message.channel_id, message.id)
-                        .components(Some(&[]))?.await?.model().await?;
     }
 
-    let mut embeds: Vec<Embed> = Vec::new();
-    for user in &users.as_slice()[1..users.len()-1] {
-        let id = user.parse::<u64>().unwrap();
-        let _  = bot.update_user(Id::new(id), -1).await;
-        embeds.push(EmbedBuilder::new()
+    let embeds = users.iter()
+        .map(|user| EmbedBuilder::new()
             .color(0x50C878)
             .title("Report")
-            .description(format!("Report issued by <@{}> against <@{}>: \n`{}`", reporter.get(), user, report))
-            .build());
+            .description(format!("Report issued by <@{}> against <@{}> from session <#{}>: \n`{}`", reporter.get(), user.get(), draft.session.get(), report))
+            .build())
+        .collect::<Vec<Embed>>();
+
+    if let Err(error) = log_report(bot, &embeds).await {
+        tracing::warn!(?error, "could not log report");
+    }
+
+    Ok(())
+}
+
+/// Rebuilds a report poll's menu from the members still left on it.
+async fn refresh_poll(
+    interaction: &Interaction,
+    bot: &Bot,
+    token: &str,
+    remaining: &[Id<UserMarker>],
+    channel_id: Id<ChannelMarker>,
+    message_id: Id<MessageMarker>,
+) -> Result<()> {
+    let mut members: Vec<User> = Vec::new();
+    for id in remaining {
+        members.push(bot.fetch_user(*id).await?);
+    }
+
+    // Keep any rows after the select menu, such as the requeue buttons.
+    let mut components = interaction.message.as_ref()
+        .map(|i| i.components.iter().skip(1).cloned().collect::<Vec<Component>>())
+        .unwrap_or_default();
+
+    if !members.is_empty() {
+        components.insert(0, get_select_row(token, members));
     }
 
-    let channel_id = Id::new(std::env::var("LOG")
-                                        .context("Log channel not set")?
-                                        .parse::<u64>()
-                                        .unwrap());
+    bot.client.update_message(channel_id, message_id)
+        .components(Some(&components))?
+        .await?;
 
-    bot.client.create_message(channel_id)
-        .embeds(embeds.as_slice())?
+    Ok(())
+}
+
+async fn log_report(bot: &Bot, embeds: &[Embed]) -> Result<()> {
+    bot.client.create_message(log_channel()?)
+        .embeds(embeds)?
         .await?;
+
     Ok(())
 }
 
+/// The report poll sent after a session: a select menu of members, then a modal for the reason.
+pub struct Report;
+
+impl ComponentHandler for Report {
+    const TAGS: &'static [&'static str] = &["draft", "draftmodal"];
+
+    fn handle(interaction: Interaction, data: ComponentData, id: CustomId, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(async move {
+            match (data, id) {
+                (ComponentData::Message(data), CustomId::Report(token)) if data.component_type == ComponentType::SelectMenu => {
+                    handle_report(interaction, bot, &token, data.values.as_slice()).await
+                },
+                (ComponentData::Modal(data), CustomId::ReportModal(token)) => handle_text(interaction, data, bot, &token).await,
+                _ => Err(DecodeError::Malformed.into()),
+            }
+        })
+    }
+}
@@ -0,0 +1,137 @@
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::{
+    application::interaction::{application_command::CommandData, Interaction},
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    channel::message::MessageFlags,
+    id::{
+        Id,
+        marker::UserMarker,
+    },
+};
+use twilight_util::builder::{
+    InteractionResponseDataBuilder,
+    embed::EmbedBuilder,
+};
+
+use crate::Bot;
+use crate::error::{BotError, Result};
+use crate::interactions::rep::log_channel;
+use crate::permissions::Permission;
+use crate::registry::{HandlerFuture, SlashCommand};
+
+pub const NAME: &str = "rep";
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "rep", desc = "manage reputation")]
+pub enum RepCommand {
+    #[command(name = "admin")]
+    Admin(RepAdmin),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "admin", desc = "adjust a user's reputation")]
+pub enum RepAdmin {
+    #[command(name = "add")]
+    Add(RepAdd),
+    #[command(name = "set")]
+    Set(RepSet),
+    #[command(name = "reset")]
+    Reset(RepReset),
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "add", desc = "add to (or remove from) a user's reputation")]
+pub struct RepAdd {
+    /// User to adjust
+    user: Id<UserMarker>,
+    /// Amount to add, negative to remove
+    #[command(min_value = -10000, max_value = 10000)]
+    amount: i64,
+    /// Why the change is being made
+    reason: String,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "set", desc = "set a user's reputation")]
+pub struct RepSet {
+    /// User to adjust
+    user: Id<UserMarker>,
+    /// New reputation
+    #[command(min_value = -10000, max_value = 10000)]
+    amount: i64,
+    /// Why the change is being made
+    reason: String,
+}
+
+#[derive(CommandModel, CreateCommand)]
+#[command(name = "reset", desc = "reset a user's reputation to zero")]
+pub struct RepReset {
+    /// User to reset
+    user: Id<UserMarker>,
+    /// Why the change is being made
+    reason: String,
+}
+
+impl RepCommand {
+    pub async fn handle(
+        interaction: Interaction,
+        data: CommandData,
+        bot: &Bot,
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+
+        let acknolewedge = InteractionResponse {
+            kind: InteractionResponseType::DeferredChannelMessageWithSource,
+            data: Some(InteractionResponseDataBuilder::new()
+                       .flags(MessageFlags::EPHEMERAL)
+                       .build()),
+        };
+
+        client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
+
+        let moderator = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let RepCommand::Admin(admin) = RepCommand::from_interaction(data.into())?;
+
+        let (user, action, reason, (old, new)) = match admin {
+            RepAdmin::Add(add) => {
+                let amount = add.amount as i32;
+                let change = bot.storage.adjust_rep(add.user, moderator, "add", Box::new(move |rep| rep + amount), &add.reason).await?;
+                (add.user, format!("add {}", amount), add.reason, change)
+            },
+            RepAdmin::Set(set) => {
+                let amount = set.amount as i32;
+                let change = bot.storage.adjust_rep(set.user, moderator, "set", Box::new(move |_| amount), &set.reason).await?;
+                (set.user, format!("set {}", amount), set.reason, change)
+            },
+            RepAdmin::Reset(reset) => {
+                let change = bot.storage.adjust_rep(reset.user, moderator, "reset", Box::new(|_| 0), &reset.reason).await?;
+                (reset.user, "reset".to_owned(), reset.reason, change)
+            },
+        };
+
+        let notice = EmbedBuilder::new()
+            .color(0x63c5da)
+            .title("Reputation changed")
+            .description(format!("<@{}> changed the reputation of <@{}> (`{}`): `{}` → `{}`\nReason: `{}`",
+                                 moderator.get(), user.get(), action, old, new, reason))
+            .build();
+
+        client.create_followup(&interaction.token).embeds(std::slice::from_ref(&notice))?.await?;
+
+        bot.client.create_message(log_channel()?)
+            .embeds(&[notice])?
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl SlashCommand for RepCommand {
+    const NAME: &'static str = NAME;
+    const PERMISSION: Permission = Permission::Moderator;
+
+    fn handle(interaction: Interaction, data: CommandData, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(Self::handle(interaction, data, bot))
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use twilight_model::{
+    application::interaction::Interaction,
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    channel::message::{
+        MessageFlags,
+        embed::Embed,
+        component::{
+            ActionRow,
+            Button,
+            ButtonStyle,
+            Component,
+            ComponentType,
+        },
+    },
+    id::{
+        Id,
+        marker::{
+            ChannelMarker,
+            MessageMarker,
+            UserMarker,
+        }
+    },
+};
+use twilight_util::builder::{
+    InteractionResponseDataBuilder,
+    embed::EmbedBuilder,
+};
+
+use crate::{Bot, PendingRequeue, QueueEntry};
+use crate::custom_id::{CustomId, DecodeError};
+use crate::error::{BotError, Result};
+use crate::interactions::queue::Queue;
+use crate::matchmaking::Role;
+use crate::registry::{ComponentData, ComponentHandler, HandlerFuture};
+
+/// How long the requeue buttons stay usable after a session ends.
+const REQUEUE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+pub struct Requeue;
+
+fn error_embed(description: &str) -> Embed {
+    EmbedBuilder::new()
+        .color(0xEE4B2B)
+        .title("Error")
+        .description(description)
+        .build()
+}
+
+/// Forgets finished sessions whose requeue buttons have run out.
+fn expire(requeues: &mut HashMap<Id<ChannelMarker>, PendingRequeue>) {
+    requeues.retain(|_, i| i.created.elapsed() < REQUEUE_TIMEOUT);
+}
+
+impl Requeue {
+    pub fn get_action_row(thread: Id<ChannelMarker>, disabled: bool) -> Component {
+        Component::ActionRow ( ActionRow {
+            components: Vec::from([Component::Button(Button {
+                custom_id: Some(CustomId::Requeue(thread).encode()),
+                disabled,
+                emoji: None,
+                label: Some("Requeue same role".to_owned()),
+                style: ButtonStyle::Primary,
+                url: None,
+            }),
+            Component::Button(Button {
+                custom_id: Some(CustomId::Together(thread).encode()),
+                disabled,
+                emoji: None,
+                label: Some("Requeue together".to_owned()),
+                style: ButtonStyle::Secondary,
+                url: None,
+            }),
+            ]),
+        })
+    }
+
+    async fn defer(interaction: &Interaction, bot: &Bot) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+
+        let acknolewedge = InteractionResponse {
+            kind: InteractionResponseType::DeferredChannelMessageWithSource,
+            data: Some(InteractionResponseDataBuilder::new()
+                       .flags(MessageFlags::EPHEMERAL)
+                       .build()),
+        };
+
+        client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
+
+        Ok(())
+    }
+
+    /// Tells a member who was waiting to requeue together that they were queued on their own.
+    async fn notify_stranded(bot: &Bot, user: Id<UserMarker>, author: Id<UserMarker>, embed: Embed, components: &[Component]) -> Result<()> {
+        let notice = EmbedBuilder::new()
+            .color(0xFFE4C4)
+            .title("Requeued on your own")
+            .description(format!("<@{}> requeued with the same role, so your group can no longer requeue together.", author.get()))
+            .build();
+
+        let channel = bot.client.create_private_channel(user).await?.model().await?;
+        bot.client.create_message(channel.id)
+            .embeds(&[notice, embed])?
+            .components(components)?
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn handle_same(
+        interaction: Interaction,
+        bot: &Bot,
+        thread: Id<ChannelMarker>,
+    ) -> Result<()> {
+        Self::defer(&interaction, bot).await?;
+
+        let client = bot.client.interaction(interaction.application_id);
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let pending = {
+            let mut requeues = bot.requeues.lock().await;
+            expire(&mut requeues);
+            match requeues.get_mut(&thread) {
+                Some(pending) => match pending.users.iter().position(|i| *i == Some(author)) {
+                    Some(slot) => {
+                        pending.users[slot] = None;
+                        pending.broken = true;
+
+                        // Anyone already waiting on the group is queued on their own instead.
+                        let mut stranded = vec![(author, slot)];
+                        for user in std::mem::take(&mut pending.ready) {
+                            if let Some(slot) = pending.users.iter().position(|i| *i == Some(user)) {
+                                pending.users[slot] = None;
+                                stranded.push((user, slot));
+                            }
+                        }
+
+                        Ok((pending.board, pending.channel, pending.queue_type, stranded))
+                    },
+                    None => Err("You were not a member of this session."),
+                },
+                None => Err("This session can no longer be requeued. Please use the queue board."),
+            }
+        };
+
+        let (board, channel, queue_type, stranded) = match pending {
+            Ok(pending) => pending,
+            Err(description) => {
+                client.create_followup(&interaction.token).embeds(&[error_embed(description)])?.await?;
+
+                return Ok(());
+            }
+        };
+
+        // Everyone is queued and told before any session starts, so one failure can't strand the rest.
+        let mut formed = Vec::new();
+        for (user, slot) in stranded {
+            let (embed, components) = match Queue::join(bot, board, user, Role::for_slot(queue_type, slot)).await {
+                Ok((embed, components, groups)) => {
+                    formed.extend(groups);
+                    (embed, components)
+                },
+                Err(error) => {
+                    tracing::warn!(?error, user = user.get(), "could not requeue a session member");
+                    (error_embed("You could not be put back in the queue. Please use the queue board."), Vec::new())
+                },
+            };
+
+            if user == author {
+                client.create_followup(&interaction.token).embeds(&[embed])?.components(&components)?.await?;
+            } else if let Err(error) = Self::notify_stranded(bot, user, author, embed, &components).await {
+                tracing::warn!(?error, user = user.get(), "could not tell a member they were requeued");
+            }
+        }
+
+        Queue::open_sessions(bot, channel, board, formed).await;
+
+        Ok(())
+    }
+
+    pub async fn handle_together(
+        interaction: Interaction,
+        bot: &Bot,
+        thread: Id<ChannelMarker>,
+    ) -> Result<()> {
+        Self::defer(&interaction, bot).await?;
+
+        let client = bot.client.interaction(interaction.application_id);
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let status = {
+            let mut requeues = bot.requeues.lock().await;
+            expire(&mut requeues);
+            let status = match requeues.get_mut(&thread) {
+                Some(pending) if !pending.users.contains(&Some(author)) => Err("You were not a member of this session, or have already requeued."),
+                Some(pending) if pending.broken || pending.users.contains(&None) => Err("Your group is no longer complete. Please use \"Requeue same role\" instead."),
+                Some(pending) => {
+                    if !pending.ready.contains(&author) {
+                        pending.ready.push(author);
+                    }
+
+                    Ok((pending.ready.len(), pending.users.len()))
+                },
+                None => Err("This session can no longer be requeued. Please use the queue board."),
+            };
+
+            match status {
+                Ok((ready, total)) if ready >= total => Ok((ready, total, requeues.remove(&thread))),
+                Ok((ready, total)) => Ok((ready, total, None)),
+                Err(description) => Err(description),
+            }
+        };
+
+        let embed = match status {
+            Err(description) => error_embed(description),
+            Ok((ready, total, None)) => EmbedBuilder::new()
+                .color(0x50C878)
+                .title("Waiting")
+                .description(format!("Waiting for the rest of your group to requeue together.\nReady: `{}/{}`", ready, total))
+                .build(),
+            Ok((_, _, Some(pending))) => {
+                let users = pending.users.iter().flatten().copied().collect::<Vec<Id<UserMarker>>>();
+
+                // Anyone may have joined the board and been matched elsewhere while the others got ready.
+                let mut busy = Vec::new();
+                for user in &users {
+                    if bot.storage.in_thread(*user).await? {
+                        busy.push(format!("<@{}>", user.get()));
+                    }
+                }
+
+                let paused = match bot.board(pending.board).await {
+                    Some(queue) => {
+                        let mut queue = queue.lock().await;
+                        if !queue.paused && busy.is_empty() {
+                            for user in &users {
+                                queue.queues.pop(user);
+                            }
+                        }
+
+                        queue.paused
+                    },
+                    None => false,
+                };
+
+                let problem = if paused {
+                    Some("The queue is paused for maintenance, so your group could not requeue together. Please try again later.".to_owned())
+                } else if !busy.is_empty() {
+                    Some(format!("{} already joined another session, so your group could not requeue together. Please use the queue board instead.", busy.join(" ")))
+                } else {
+                    None
+                };
+
+                match problem {
+                    Some(description) => {
+                        // The others were only told they are waiting, so they hear about it in the board's channel.
+                        let others = users.iter()
+                            .filter(|i| **i != author)
+                            .map(|i| format!("<@{}>", i.get()))
+                            .collect::<Vec<String>>()
+                            .join(" ");
+
+                        bot.client.create_message(pending.channel)
+                            .content(&others)?
+                            .embeds(&[error_embed(&description)])?
+                            .await?;
+
+                        error_embed(&description)
+                    },
+                    None => {
+                        let group = users.into_iter().map(|i| QueueEntry::new(i, None)).collect::<Vec<QueueEntry>>();
+                        Queue::create_session(bot, pending.channel, pending.board, pending.queue_type, group).await?;
+
+                        EmbedBuilder::new()
+                            .color(0x50C878)
+                            .title("Success")
+                            .description("Your group is back together. A new thread has been created.")
+                            .build()
+                    },
+                }
+            },
+        };
+
+        client.create_followup(&interaction.token).embeds(&[embed])?.await?;
+
+        Ok(())
+    }
+}
+
+impl Bot {
+    /// Remembers a finished session so its members can requeue, returning false if it can't be.
+    pub async fn register_requeue(
+        &self,
+        thread: Id<ChannelMarker>,
+        channel: Option<Id<ChannelMarker>>,
+        board: Option<Id<MessageMarker>>,
+        queue_type: Option<bool>,
+        users: Vec<Option<Id<UserMarker>>>,
+    ) -> bool {
+        let (Some(channel), Some(board), Some(queue_type)) = (channel, board, queue_type) else {
+            return false;
+        };
+
+        let mut requeues = self.requeues.lock().await;
+        expire(&mut requeues);
+        requeues.entry(thread).or_insert(PendingRequeue {
+            board,
+            channel,
+            queue_type,
+            users,
+            ready: Vec::new(),
+            broken: false,
+            created: Instant::now(),
+        });
+
+        true
+    }
+}
+
+impl ComponentHandler for Requeue {
+    const TAGS: &'static [&'static str] = &["requeue", "together"];
+
+    fn handle(interaction: Interaction, data: ComponentData, id: CustomId, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(async move {
+            match (data.component_type(), id) {
+                (Some(ComponentType::Button), CustomId::Requeue(thread)) => Self::handle_same(interaction, bot, thread).await,
+                (Some(ComponentType::Button), CustomId::Together(thread)) => Self::handle_together(interaction, bot, thread).await,
+                _ => Err(DecodeError::Malformed.into()),
+            }
+        })
+    }
+}
@@ -1,4 +1,3 @@
-use anyhow::Context;
 use twilight_interactions::command::{CommandModel, CreateCommand};
 use twilight_model::{
     application::interaction::{application_command::CommandData, Interaction},
@@ -6,24 +5,25 @@ use twilight_model::{
     channel::{ChannelType::GuildText,
                 message::MessageFlags,
     },
-    guild::Permissions,
 };
 use twilight_util::builder::{
     InteractionResponseDataBuilder,
     embed::EmbedBuilder,
 };
 
-use crate::{Bot, CombinedQueues};
-use crate::interactions::queue;
+use crate::Bot;
+use crate::error::{BotError, Result};
+use crate::interactions::{board::BoardKind, queue};
+use crate::permissions::Permission;
+use crate::registry::{HandlerFuture, SlashCommand};
 
 pub const NAME: &str = "setup";
 
 #[derive(CommandModel, CreateCommand)]
-#[command(name = "setup", desc = "Sends Setup Message", default_permissions = "admin_perms")]
-pub struct Setup;
-
-fn admin_perms() -> Permissions {
-    Permissions::ADMINISTRATOR
+#[command(name = "setup", desc = "Sends Setup Message", dm_permission = false)]
+pub struct Setup {
+    /// What the board is for
+    kind: BoardKind,
 }
 
 
@@ -32,7 +32,7 @@ impl Setup {
         interaction: Interaction,
         data: CommandData,
         bot: &Bot,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let client = bot.client.interaction(interaction.application_id);
 
         let acknolewedge = InteractionResponse {
@@ -44,7 +44,9 @@ impl Setup {
 
         client.create_response(interaction.id, &interaction.token, &acknolewedge).await?;
 
-        let channel = interaction.channel.context("Could not get message channel. Is this in a channel?")?;
+        let Setup { kind } = Setup::from_interaction(data.into())?;
+
+        let channel = interaction.channel.ok_or_else(|| BotError::user("Could not get message channel. Is this in a channel?"))?;
 
         if channel.kind != GuildText {
             let embed = EmbedBuilder::new()
@@ -58,20 +60,7 @@ impl Setup {
             return Ok(())
         }
 
-        let queue_embed = EmbedBuilder::new()
-            .color(0x63c5da)
-            .title("Queue")
-            .description("This is a queue bot for multiplayer Echoes farming in Wuthering Waves.
-
-Choose a queue based on your needs for the multiplayer session :-
-1. **Co-op (1)**: If you want to share elites with others who are also willing to share their elites. (+1 reputation)
-2. **Carry (2)**: If you are willing to help fight elites for others in their worlds.
-3. **Assist (3)**: If you need help in fighting elites in your own world. (+2 reputation)
-
-**Assist (3)** is recommended for people who just want to farm their own world, whereas **Co-op (1)** would suit those better who are just starting the farming session and also want to farm other people's worlds alongside their own. **Carry (2)** works best for those who have farmed their own world already but still want more echoes.
-
-Don’t forget to poll at the end of each multiplayer session for reputation points! These points will be useful soon…")
-            .build();
+        let queue_embed = kind.embed();
 
         let message = bot.client
             .create_message(channel.id)
@@ -81,14 +70,14 @@ Don’t forget to poll at the end of each multiplayer session for reputation poi
             .await?;
 
         bot.client.update_message(channel.id, message.id)
-            .components(Some(&[queue::Queue::get_action_row()]))?
+            .components(Some(&[queue::Queue::get_action_row(kind, false)]))?
             .await?;
 
-        bot.queues.lock().await.insert(message.id, CombinedQueues {
-            queue_a: Vec::with_capacity(3),
-            queue_b: Vec::new(),
-            queue_c: Vec::new(),
-        });
+        if let Some(guild_id) = interaction.guild_id {
+            bot.storage.insert_board(message.id, channel.id, guild_id, kind).await?;
+        }
+
+        bot.board_or_insert(message.id, kind, false).await;
 
 
         let embed = EmbedBuilder::new()
@@ -103,3 +92,12 @@ Don’t forget to poll at the end of each multiplayer session for reputation poi
     }
 }
 
+
+impl SlashCommand for Setup {
+    const NAME: &'static str = NAME;
+    const PERMISSION: Permission = Permission::Moderator;
+
+    fn handle(interaction: Interaction, data: CommandData, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(Self::handle(interaction, data, bot))
+    }
+}
@@ -0,0 +1,226 @@
+use twilight_model::{
+    application::interaction::Interaction,
+    http::interaction::{InteractionResponse, InteractionResponseType},
+    channel::message::{
+        MessageFlags,
+        component::{
+            ActionRow,
+            Component,
+            ComponentType,
+            SelectMenu,
+            SelectMenuOption,
+        },
+    },
+    id::{
+        Id,
+        marker::MessageMarker,
+    },
+};
+use twilight_util::builder::{
+    InteractionResponseDataBuilder,
+    embed::EmbedBuilder,
+};
+
+use crate::Bot;
+use crate::error::{BotError, Result};
+use crate::custom_id::{CustomId, DecodeError};
+use crate::interactions::board::BoardKind;
+use crate::registry::{ComponentData, ComponentHandler, HandlerFuture};
+
+/// What a target is, which decides the boards it is offered on.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TargetKind {
+    Elite,
+    Overlord,
+    WeeklyBoss,
+    EchoSet,
+}
+
+impl TargetKind {
+    fn description(&self) -> &'static str {
+        match self {
+            TargetKind::Elite => "Elite",
+            TargetKind::Overlord => "Overlord",
+            TargetKind::WeeklyBoss => "Weekly boss",
+            TargetKind::EchoSet => "Echo set",
+        }
+    }
+
+    /// Whether players on a board of `kind` can farm for this. Each board stays within Discord's
+    /// limit of 25 options per select menu.
+    fn offered_on(&self, kind: BoardKind) -> bool {
+        match kind {
+            BoardKind::Elite => matches!(self, TargetKind::Elite | TargetKind::EchoSet),
+            BoardKind::Overlord => matches!(self, TargetKind::Overlord | TargetKind::EchoSet),
+            BoardKind::Weekly => matches!(self, TargetKind::WeeklyBoss | TargetKind::EchoSet),
+            BoardKind::FreeCoop => *self != TargetKind::EchoSet,
+        }
+    }
+}
+
+/// Enemies and Echo sets players can say they are farming for.
+const TARGETS: [(&str, TargetKind); 33] = [
+    ("Violet-Feathered Heron", TargetKind::Elite),
+    ("Cyan-Feathered Heron", TargetKind::Elite),
+    ("Stonewall Bracer", TargetKind::Elite),
+    ("Flautist", TargetKind::Elite),
+    ("Tambourinist", TargetKind::Elite),
+    ("Rocksteady Guardian", TargetKind::Elite),
+    ("Chaserazor", TargetKind::Elite),
+    ("Roseshroom", TargetKind::Elite),
+    ("Havoc Dreadmane", TargetKind::Elite),
+    ("Spearback", TargetKind::Elite),
+    ("Autopuppet Scout", TargetKind::Elite),
+    ("Carapace", TargetKind::Elite),
+    ("Crownless", TargetKind::Overlord),
+    ("Thundering Mephis", TargetKind::Overlord),
+    ("Tempest Mephis", TargetKind::Overlord),
+    ("Inferno Rider", TargetKind::Overlord),
+    ("Feilian Beringal", TargetKind::Overlord),
+    ("Mourning Aix", TargetKind::Overlord),
+    ("Impermanence Heron", TargetKind::Overlord),
+    ("Lampylumen Myriad", TargetKind::Overlord),
+    ("Mech Abomination", TargetKind::Overlord),
+    ("Bell-Borne Geochelone", TargetKind::WeeklyBoss),
+    ("Dreamless", TargetKind::WeeklyBoss),
+    ("Jué", TargetKind::WeeklyBoss),
+    ("Freezing Frost", TargetKind::EchoSet),
+    ("Molten Rift", TargetKind::EchoSet),
+    ("Void Thunder", TargetKind::EchoSet),
+    ("Sierra Gale", TargetKind::EchoSet),
+    ("Celestial Light", TargetKind::EchoSet),
+    ("Sun-sinking Eclipse", TargetKind::EchoSet),
+    ("Rejuvenating Glow", TargetKind::EchoSet),
+    ("Moonlit Clouds", TargetKind::EchoSet),
+    ("Lingering Tunes", TargetKind::EchoSet),
+];
+
+/// The targets offered on a board of `kind`, in menu order.
+fn targets(kind: BoardKind) -> impl Iterator<Item = &'static (&'static str, TargetKind)> {
+    TARGETS.iter().filter(move |(_, target)| target.offered_on(kind))
+}
+
+pub struct Targets;
+
+impl Targets {
+    pub fn get_select_row(board: Id<MessageMarker>, kind: BoardKind) -> Component {
+        let options = targets(kind)
+            .map(|(name, target)| SelectMenuOption {
+                default: false,
+                description: Some(target.description().to_owned()),
+                emoji: None,
+                label: name.to_string(),
+                value: name.to_string(),
+            })
+            .collect::<Vec<SelectMenuOption>>();
+
+        Component::ActionRow(ActionRow {
+            components: vec![Component::SelectMenu(SelectMenu {
+                custom_id: CustomId::Targets(board).encode(),
+                disabled: false,
+                max_values: Some(options.len() as u8),
+                min_values: Some(0),
+                options,
+                placeholder: Some("Optional: choose what you are farming for".to_owned()),
+            })],
+        })
+    }
+
+    pub async fn handle_select(
+        interaction: Interaction,
+        bot: &Bot,
+        board: Id<MessageMarker>,
+        values: &[String],
+    ) -> Result<()> {
+        let client = bot.client.interaction(interaction.application_id);
+        let author = interaction.author_id().ok_or(BotError::Missing("interaction author"))?;
+
+        let updated = match bot.board(board).await {
+            Some(queue) => {
+                let mut queue = queue.lock().await;
+                let kind = queue.kind;
+                match queue.queues.entry_mut(&author) {
+                    Some(entry) => {
+                        // Anything not offered on this board did not come from us, so it is dropped rather than trusted.
+                        entry.targets = values.iter()
+                            .filter(|i| targets(kind).any(|(name, _)| name == i))
+                            .cloned()
+                            .collect();
+                        Some(entry.targets.clone())
+                    },
+                    None => None,
+                }
+            },
+            None => None,
+        };
+
+        let embed = match updated {
+            None => EmbedBuilder::new()
+                .color(0xEE4B2B)
+                .title("Error")
+                .description("You are no longer waiting in this queue.")
+                .build(),
+            Some(targets) if targets.is_empty() => EmbedBuilder::new()
+                .color(0x50C878)
+                .title("Success")
+                .description("Cleared your targets.")
+                .build(),
+            Some(targets) => EmbedBuilder::new()
+                .color(0x50C878)
+                .title("Success")
+                .description(format!("You will preferably be grouped with players farming for: {}", targets.join(", ")))
+                .build(),
+        };
+
+        let data = InteractionResponseDataBuilder::new()
+                       .embeds([embed])
+                       .flags(MessageFlags::EPHEMERAL)
+                       .build();
+
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(data),
+        };
+
+        client.create_response(interaction.id, &interaction.token, &response).await?;
+
+        Ok(())
+    }
+}
+
+impl ComponentHandler for Targets {
+    const TAGS: &'static [&'static str] = &["targets"];
+
+    fn handle(interaction: Interaction, data: ComponentData, id: CustomId, bot: &Bot) -> HandlerFuture<'_> {
+        Box::pin(async move {
+            match (data.component_type(), id) {
+                (Some(ComponentType::SelectMenu), CustomId::Targets(board)) => Self::handle_select(interaction, bot, board, data.values()).await,
+                _ => Err(DecodeError::Malformed.into()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_board_fits_in_one_menu() {
+        for kind in [BoardKind::Elite, BoardKind::Overlord, BoardKind::Weekly, BoardKind::FreeCoop] {
+            let count = targets(kind).count();
+            assert!((1..=25).contains(&count), "{:?} offers {} targets", kind, count);
+        }
+    }
+
+    #[test]
+    fn boards_offer_their_own_enemies() {
+        let offered = |kind: BoardKind, name: &str| targets(kind).any(|(i, _)| *i == name);
+
+        assert!(offered(BoardKind::Elite, "Flautist"));
+        assert!(!offered(BoardKind::Elite, "Crownless"));
+        assert!(offered(BoardKind::Overlord, "Crownless"));
+        assert!(!offered(BoardKind::Overlord, "Flautist"));
+        assert!(offered(BoardKind::Weekly, "Dreamless"));
+    }
+}
@@ -0,0 +1,136 @@
+pub mod interactions;
+pub mod matchmaking;
+mod handle;
+mod permissions;
+mod custom_id;
+pub mod error;
+pub mod reconcile;
+pub mod registry;
+pub mod scheduler;
+pub mod storage;
+
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::mem;
+use std::time::Instant;
+
+use twilight_http::Client;
+use twilight_model::{
+    id::{
+        Id,
+        marker::{
+            ChannelMarker,
+            UserMarker,
+            MessageMarker,
+        }
+    },
+};
+
+use tokio::sync::Mutex;
+
+use crate::interactions::board::BoardKind;
+use crate::matchmaking::{Entry, Event, Group, Queues};
+use crate::registry::Registry;
+use crate::scheduler::Scheduler;
+use crate::storage::Storage;
+
+pub struct Bot {
+    pub storage: Box<dyn Storage>,
+    pub client: Client,
+    queues: Arc<Mutex<HashMap<Id<MessageMarker>, BoardQueues>>>,
+    requeues: Arc<Mutex<HashMap<Id<ChannelMarker>, PendingRequeue>>>,
+    pub registry: Registry,
+    pub scheduler: Scheduler,
+}
+
+impl Bot {
+    pub fn new(client: Client, storage: Box<dyn Storage>) -> Self {
+        Self {
+            storage,
+            client,
+            queues: Arc::new(Mutex::new(HashMap::new())),
+            requeues: Arc::new(Mutex::new(HashMap::new())),
+            registry: Registry::new(),
+            scheduler: Scheduler::new(),
+        }
+    }
+
+    /// The queues of a board, if it was loaded or clicked since startup.
+    pub async fn board(&self, board: Id<MessageMarker>) -> Option<BoardQueues> {
+        self.queues.lock().await.get(&board).cloned()
+    }
+
+    /// The queues of a board, created empty with `kind` and `paused` if it hasn't been seen yet.
+    pub async fn board_or_insert(&self, board: Id<MessageMarker>, kind: BoardKind, paused: bool) -> BoardQueues {
+        let mut queues = self.queues.lock().await;
+        Arc::clone(queues.entry(board).or_insert_with(|| Arc::new(Mutex::new(CombinedQueues::new(kind, paused)))))
+    }
+
+    /// The queues of every known board.
+    pub async fn boards(&self) -> Vec<(Id<MessageMarker>, BoardQueues)> {
+        self.queues.lock().await.iter().map(|(board, queue)| (*board, Arc::clone(queue))).collect()
+    }
+}
+
+/// Builds the REST client. `api` replaces Discord's host, e.g. `http://127.0.0.1:8080` for a local proxy or test server.
+pub fn discord_client(token: String, api: Option<&str>) -> Client {
+    let builder = Client::builder().token(token);
+
+    let builder = match api.map(|i| i.trim_end_matches('/')) {
+        Some(api) => match api.strip_prefix("http://") {
+            Some(host) => builder.proxy(host.to_owned(), true),
+            None => builder.proxy(api.trim_start_matches("https://").to_owned(), false),
+        },
+        None => builder,
+    };
+
+    builder.build()
+}
+
+/// A finished session whose members can still requeue from its closing message or DM.
+#[derive(Debug)]
+pub struct PendingRequeue {
+    board: Id<MessageMarker>,
+    channel: Id<ChannelMarker>,
+    queue_type: bool,
+    users: Vec<Option<Id<UserMarker>>>,
+    ready: Vec<Id<UserMarker>>,
+    broken: bool,
+    created: Instant,
+}
+
+pub type QueueEntry = Entry<Id<UserMarker>>;
+
+/// One board's queues behind their own lock, so a click on one board never waits on another.
+/// The map in `Bot` is only locked long enough to look a board up.
+pub type BoardQueues = Arc<Mutex<CombinedQueues>>;
+
+#[derive(Debug)]
+pub struct CombinedQueues {
+    queues: Queues<Id<UserMarker>>,
+    kind: BoardKind,
+    paused: bool,
+}
+
+impl CombinedQueues {
+    pub fn new(kind: BoardKind, paused: bool) -> Self {
+        Self {
+            queues: Queues::default(),
+            kind,
+            paused,
+        }
+    }
+
+    /// Who is waiting, for reading. Joins and leaves go through `apply`.
+    pub fn queues(&self) -> &Queues<Id<UserMarker>> {
+        &self.queues
+    }
+
+    /// Applies an event with the board's group size, returning every group that formed.
+    pub fn apply(&mut self, event: Event<Id<UserMarker>>) -> Vec<Group<Id<UserMarker>>> {
+        let (queues, groups) = matchmaking::apply(mem::take(&mut self.queues), self.kind.coop_size(), event);
+        self.queues = queues;
+
+        groups
+    }
+}
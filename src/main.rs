@@ -1,87 +1,46 @@
-mod interactions;
-mod handle;
-mod database;
-
-use std::{env, sync::{Arc}};
-use std::collections::HashMap;
+use std::{env, sync::{Arc}, time::Duration};
 
 use anyhow::Context;
+use tokio::{signal, task::JoinSet, time::timeout};
 use tracing::Level;
-use sqlx::PgPool;
 use twilight_gateway::{
     stream::{self, ShardEventStream},
-    Config, Intents,
-    EventTypeFlags,
+    CloseFrame, Config, Intents,
+    EventTypeFlags, Message, Session,
 };
 use futures_util::StreamExt;
-use twilight_http::Client;
-use twilight_model::{
-    gateway::{
-        payload::outgoing::update_presence::UpdatePresencePayload,
-        presence::{ActivityType, MinimalActivity, Status},
-    },
-    id::{
-        Id,
-        marker::{
-            UserMarker,
-            MessageMarker,
-        }
-    },
+use twilight_model::gateway::{
+    payload::outgoing::update_presence::UpdatePresencePayload,
+    presence::{ActivityType, MinimalActivity, Status},
 };
 
-use twilight_interactions::command::CreateCommand;
-use tokio::sync::Mutex;
+use ww_bot::{discord_client, reconcile::{Reconcile, Rules}, scheduler::JobHandler, storage::{self, GatewaySession}, Bot};
 
-use crate::{interactions::{ping::Ping, setup::Setup, end::End}};
-
-pub struct Bot {
-    db: PgPool,
-    client: Client,
-    queues: Arc<Mutex<HashMap<Id<MessageMarker>, CombinedQueues>>>,
-}
-
-#[derive(Debug)]
-pub struct CombinedQueues {
-    queue_a: Vec<Id<UserMarker>>,
-    queue_b: Vec<Id<UserMarker>>,
-    queue_c: Vec<Id<UserMarker>>,
-}
-
-impl CombinedQueues {
-    pub fn contains(&self, id: &Id<UserMarker>) -> bool {
-        self.queue_a.contains(id) || self.queue_b.contains(id) || self.queue_c.contains(id)
-    }
-
-    pub fn pop(&mut self, id: &Id<UserMarker>) {
-        self.queue_a.retain(|i| *i != *id);
-        self.queue_b.retain(|i| *i != *id);
-        self.queue_c.retain(|i| *i != *id);
-    }
-}
+/// `docker stop` kills the container ten seconds after SIGTERM, so leave time to save and close.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(8);
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let token = env::var("TOKEN").context("Bot token is not set")?;
     let database_url = env::var("DATABASE_URL").context("Database url is not set")?;
 
-    let db = PgPool::connect(&database_url).await?;
+    let storage = storage::connect(&database_url).await?;
+    let client = discord_client(token.clone(), env::var("DISCORD_API").ok().as_deref());
 
     tracing_subscriber::fmt()
         .compact()
         .with_max_level(Level::INFO)
         .init();
 
-    let bot = Arc::new( Bot {
-        client: Client::new(token.clone()),
-        db,
-        queues: Arc::new(Mutex::new(HashMap::new())),
-    });
+    let bot = Arc::new(Bot::new(client, storage));
 
-    let _ = bot.setup_database().await;
+    bot.storage.setup().await.context("Could not set up storage")?;
+    bot.load_boards().await?;
 
     let config = Config::builder(token.clone(),
-                                 Intents::GUILDS)
+                                 Intents::GUILDS | Intents::GUILD_MEMBERS)
         .event_types(EventTypeFlags::THREAD_UPDATE |
+                     EventTypeFlags::THREAD_MEMBERS_UPDATE |
                      EventTypeFlags::INTERACTION_CREATE |
                      EventTypeFlags::GATEWAY_HELLO |
                      EventTypeFlags::GATEWAY_HEARTBEAT |
@@ -91,48 +50,171 @@ async fn main() -> anyhow::Result<()> {
         .presence(presence())
         .build();
 
-    let commands = [
-        Ping::create_command().into(),
-        Setup::create_command().into(),
-        End::create_command().into(),
-    ];
-
     let application = bot.client.current_user_application().await?.model().await?;
     let interaction_client = bot.client.interaction(application.id);
 
     tracing::info!("logged as {} with ID {}", application.name, application.id);
 
-    if let Err(error) = interaction_client.set_global_commands(&commands).await {
+    if let Err(error) = interaction_client.set_global_commands(&bot.registry.commands()).await {
         tracing::error!(?error, "failed to register commands");
     }
 
-    let mut shards = stream::create_recommended(&bot.client, config, |_id, builder| builder.build())
+    // Resuming replays whatever happened while the bot was down, as long as Discord still has the session.
+    let sessions = bot.storage.take_gateway_sessions().await?;
+
+    let mut shards = stream::create_recommended(&bot.client, config, |id, builder| {
+        match sessions.iter().find(|i| i.shard == id.number() && i.total == id.total()) {
+            Some(saved) => builder.session(Session::new(saved.sequence, saved.session_id.clone())).build(),
+            None => builder.build(),
+        }
+    })
         .await?
         .collect::<Vec<_>>();
-    let mut stream = ShardEventStream::new(shards.iter_mut());
 
-    while let Some((shard, event)) = stream.next().await {
-        let bot_ref = Arc::clone(&bot);
-        let event = match event {
-            Ok(event) => event,
-            Err(error) => {
-                if error.is_fatal() {
-                    tracing::error!(?error, "fatal error while receiving event");
-                    break;
+    tracing::info!(shards = shards.len(), saved_sessions = sessions.len(), "connecting");
+
+    let mut tasks = JoinSet::new();
+
+    // Later passes are a scheduled job, so only one instance runs each of them.
+    let rules = Rules::from_env();
+    match rules.interval {
+        Some(interval) => bot.schedule_every(Reconcile::KIND, Reconcile::KIND, "", interval).await?,
+        None => for job in bot.storage.get_jobs().await?.into_iter().filter(|i| i.name == Reconcile::KIND) {
+            bot.storage.cancel_job(job.id).await?;
+        },
+    }
+
+    // Shards that could not resume, or were down too long for it, may have missed thread events.
+    let bot_ref = Arc::clone(&bot);
+    let reconciler = tokio::spawn(async move {
+        if let Err(error) = bot_ref.reconcile(&rules).await {
+            tracing::error!(?error, "failed to reconcile threads");
+        }
+    });
+
+    let bot_ref = Arc::clone(&bot);
+    let scheduler = tokio::spawn(async move {
+        bot_ref.run_scheduler().await;
+    });
+
+    let bot_ref = Arc::clone(&bot);
+    let ticks = tokio::spawn(async move {
+        bot_ref.run_queue_ticks().await;
+    });
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    {
+        let mut stream = ShardEventStream::new(shards.iter_mut());
+
+        loop {
+            let (shard, event) = tokio::select! {
+                _ = &mut shutdown => break,
+                Some(_) = tasks.join_next(), if !tasks.is_empty() => continue,
+                next = stream.next() => match next {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+
+            let bot_ref = Arc::clone(&bot);
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    if error.is_fatal() {
+                        tracing::error!(?error, "fatal error while receiving event");
+                        break;
+                    }
+
+                    tracing::warn!(?error, "error while receiving event");
+                    continue;
                 }
+            };
+
+            tracing::info!(kind = ?event.kind(), shard = ?shard.id().number(), "received event");
+            tasks.spawn(async move {
+                bot_ref.process(event).await;
+            });
+        }
+    }
+
+    // No more events are read from here on, so only handlers that already started are left.
+    tracing::info!(in_flight = tasks.len(), "shutting down");
+    reconciler.abort();
+    // A job cut off here keeps its lease, so it runs again once the lease runs out.
+    scheduler.abort();
+    // Held users stay queued, and are grouped by the next instance to tick the board.
+    ticks.abort();
+
+    if timeout(SHUTDOWN_GRACE, async { while tasks.join_next().await.is_some() {} }).await.is_err() {
+        tracing::warn!(in_flight = tasks.len(), "handlers did not finish in time, aborting them");
+        tasks.shutdown().await;
+    }
+
+    if let Err(error) = bot.save_queues().await {
+        tracing::error!(?error, "failed to save queues");
+    }
+
+    // Taken before closing, so events that arrive while the shards close are replayed on resume.
+    let sessions = shards.iter()
+        .filter_map(|shard| shard.session().map(|session| GatewaySession {
+            shard: shard.id().number(),
+            total: shard.id().total(),
+            session_id: session.id().to_owned(),
+            sequence: session.sequence(),
+        }))
+        .collect::<Vec<GatewaySession>>();
+
+    if let Err(error) = bot.storage.save_gateway_sessions(&sessions).await {
+        tracing::error!(?error, "failed to save gateway sessions");
+    }
+
+    for shard in &mut shards {
+        // Unlike a normal close, this keeps the session alive on Discord's side so it can be resumed.
+        if let Err(error) = shard.close(CloseFrame::RESUME).await {
+            tracing::warn!(?error, shard = ?shard.id().number(), "failed to close shard");
+            continue;
+        }
 
-                tracing::warn!(?error, "error while receiving event");
-                continue;
+        // Discord answers with its own close frame, which ends the connection cleanly.
+        let _ = timeout(Duration::from_secs(1), async {
+            while let Ok(message) = shard.next_message().await {
+                if matches!(message, Message::Close(_)) {
+                    break;
+                }
             }
+        }).await;
+    }
+
+    bot.storage.close().await?;
+
+    tracing::info!("shut down");
+
+    Ok(())
+}
+
+/// Resolves on Ctrl-C, or on the SIGTERM sent by `docker stop`.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(error) => {
+                tracing::error!(?error, "failed to listen for SIGTERM");
+                let _ = signal::ctrl_c().await;
+                return;
+            },
         };
 
-        tracing::info!(kind = ?event.kind(), shard = ?shard.id().number(), "received event");
-        tokio::spawn(async move {
-            bot_ref.process(event).await;
-        });
+        tokio::select! {
+            _ = signal::ctrl_c() => {},
+            _ = terminate.recv() => {},
+        }
     }
 
-    Ok(())
+    #[cfg(not(unix))]
+    let _ = signal::ctrl_c().await;
 }
 
 fn presence() -> UpdatePresencePayload {
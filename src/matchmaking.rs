@@ -0,0 +1,513 @@
+//! How groups are formed from a board's queues, kept free of Discord and storage so the rules
+//! can be tested and simulated on their own.
+
+use std::time::{Duration, Instant};
+
+/// How long a co-op user with targets waits for others sharing them before being grouped with anyone.
+pub const TARGET_HOLD: Duration = Duration::from_secs(60);
+
+/// What a user queued for. Sessions are either all co-op, or two carries in an assist's world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Coop,
+    Carry,
+    Assist,
+}
+
+impl Role {
+    /// The role that fills `slot` of a session of the given type.
+    pub fn for_slot(queue_type: bool, slot: usize) -> Self {
+        match (queue_type, slot) {
+            (false, _) => Role::Coop,
+            (true, 0 | 1) => Role::Carry,
+            (true, _) => Role::Assist,
+        }
+    }
+
+    /// How the role is written in custom ids and storage.
+    pub fn value(&self) -> &'static str {
+        match self {
+            Role::Coop => "coop",
+            Role::Carry => "carry",
+            Role::Assist => "assist",
+        }
+    }
+
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "coop" => Some(Role::Coop),
+            "carry" => Some(Role::Carry),
+            "assist" => Some(Role::Assist),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry<U> {
+    pub user: U,
+    pub joined: Instant,
+    pub world_level: Option<i32>,
+    pub targets: Vec<String>,
+}
+
+impl<U> Entry<U> {
+    pub fn new(user: U, world_level: Option<i32>) -> Self {
+        Self {
+            user,
+            joined: Instant::now(),
+            world_level,
+            targets: Vec::new(),
+        }
+    }
+
+    /// How many target bosses or Echo sets two entries have in common.
+    pub fn shared_targets(&self, other: &Entry<U>) -> usize {
+        self.targets.iter().filter(|i| other.targets.contains(i)).count()
+    }
+}
+
+/// The users waiting on a board, each queue in the order they joined.
+#[derive(Clone, Debug)]
+pub struct Queues<U> {
+    pub queue_a: Vec<Entry<U>>,
+    pub queue_b: Vec<Entry<U>>,
+    pub queue_c: Vec<Entry<U>>,
+}
+
+impl<U> Default for Queues<U> {
+    fn default() -> Self {
+        Self {
+            queue_a: Vec::new(),
+            queue_b: Vec::new(),
+            queue_c: Vec::new(),
+        }
+    }
+}
+
+impl<U: PartialEq> Queues<U> {
+    pub fn queue(&self, role: Role) -> &[Entry<U>] {
+        match role {
+            Role::Coop => &self.queue_a,
+            Role::Carry => &self.queue_b,
+            Role::Assist => &self.queue_c,
+        }
+    }
+
+    fn queue_mut(&mut self, role: Role) -> &mut Vec<Entry<U>> {
+        match role {
+            Role::Coop => &mut self.queue_a,
+            Role::Carry => &mut self.queue_b,
+            Role::Assist => &mut self.queue_c,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue_a.len() + self.queue_b.len() + self.queue_c.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, user: &U) -> bool {
+        [&self.queue_a, &self.queue_b, &self.queue_c].iter()
+            .any(|queue| queue.iter().any(|i| i.user == *user))
+    }
+
+    pub fn pop(&mut self, user: &U) {
+        self.queue_a.retain(|i| i.user != *user);
+        self.queue_b.retain(|i| i.user != *user);
+        self.queue_c.retain(|i| i.user != *user);
+    }
+
+    pub fn entry_mut(&mut self, user: &U) -> Option<&mut Entry<U>> {
+        self.queue_a.iter_mut()
+            .chain(self.queue_b.iter_mut())
+            .chain(self.queue_c.iter_mut())
+            .find(|i| i.user == *user)
+    }
+
+    /// Empties every queue, returning how many users were removed.
+    pub fn clear(&mut self) -> usize {
+        let len = self.len();
+        self.queue_a.clear();
+        self.queue_b.clear();
+        self.queue_c.clear();
+        len
+    }
+
+    /// Puts an entry back in its queue without forming groups, ahead of everyone who joined after it.
+    pub fn restore(&mut self, role: Role, entry: Entry<U>) {
+        if !self.contains(&entry.user) {
+            let queue = self.queue_mut(role);
+            let index = queue.iter().position(|i| i.joined > entry.joined).unwrap_or(queue.len());
+            queue.insert(index, entry);
+        }
+    }
+
+    /// Takes the first entry waiting for the role that fills `slot` of a session whose World Level is accepted.
+    pub fn take_replacement(&mut self, queue_type: bool, slot: usize, accept: impl Fn(i32) -> bool) -> Option<Entry<U>> {
+        let queue = self.queue_mut(Role::for_slot(queue_type, slot));
+
+        let index = queue.iter().position(|i| accept(i.world_level.unwrap_or(0)))?;
+
+        Some(queue.remove(index))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Event<U> {
+    Join(Role, Entry<U>),
+    Leave(U),
+    /// Time passed, so co-op groups held for shared targets may be due.
+    Tick(Instant),
+}
+
+#[derive(Debug)]
+pub struct Group<U> {
+    /// False for a co-op group, true for two carries followed by an assist.
+    pub queue_type: bool,
+    pub members: Vec<Entry<U>>,
+}
+
+/// Applies an event to a board's queues, returning the new queues and every group formed.
+///
+/// Co-op groups need `coop_size` users. A user who is already waiting can't join again. A join
+/// happens when its entry is created, so the joining entry's `joined` is taken as the current time.
+pub fn apply<U: PartialEq>(mut queues: Queues<U>, coop_size: usize, event: Event<U>) -> (Queues<U>, Vec<Group<U>>) {
+    let (now, carry) = match event {
+        Event::Join(_, entry) if queues.contains(&entry.user) => return (queues, Vec::new()),
+        Event::Join(Role::Coop, entry) => {
+            let now = entry.joined;
+            queues.queue_a.push(entry);
+            (now, None)
+        },
+        Event::Join(role, entry) => {
+            let now = entry.joined;
+            queues.queue_mut(role).push(entry);
+            (now, carry_group(&mut queues))
+        },
+        Event::Leave(user) => {
+            queues.pop(&user);
+            (Instant::now(), None)
+        },
+        Event::Tick(now) => (now, None),
+    };
+
+    // A held user leaving, or their hold running out, can free several groups at once.
+    let mut groups = carry.into_iter().collect::<Vec<Group<U>>>();
+    while let Some(group) = coop_group(&mut queues.queue_a, coop_size, now) {
+        groups.push(group);
+    }
+
+    (queues, groups)
+}
+
+/// Takes the earliest user along with the others who share the most targets with them. An earliest
+/// user with targets waits up to `TARGET_HOLD` for enough others sharing one, and everyone behind
+/// them waits too.
+fn coop_group<U>(queue: &mut Vec<Entry<U>>, size: usize, now: Instant) -> Option<Group<U>> {
+    if queue.is_empty() || queue.len() < size {
+        return None;
+    }
+
+    let mut others = (1..queue.len()).collect::<Vec<usize>>();
+    others.sort_by_key(|i| std::cmp::Reverse(queue[0].shared_targets(&queue[*i])));
+    others.truncate(size.saturating_sub(1));
+
+    let matched = others.iter().all(|i| queue[0].shared_targets(&queue[*i]) > 0);
+    let holding = now.saturating_duration_since(queue[0].joined) < TARGET_HOLD;
+    if !queue[0].targets.is_empty() && !matched && holding {
+        return None;
+    }
+
+    others.sort_by(|a, b| b.cmp(a));
+
+    let mut members = others.into_iter().map(|i| queue.remove(i)).collect::<Vec<Entry<U>>>();
+    members.push(queue.remove(0));
+    members.reverse();
+
+    Some(Group {
+        queue_type: false,
+        members,
+    })
+}
+
+/// Pairs the earliest assist with two carries whose World Level is at least theirs,
+/// preferring carries who share the assist's targets and then the earliest.
+fn carry_group<U>(queues: &mut Queues<U>) -> Option<Group<U>> {
+    let (assist, first, second) = queues.queue_c.iter().enumerate().find_map(|(assist, entry)| {
+        let level = entry.world_level.unwrap_or(0);
+        let mut carries = queues.queue_b.iter()
+            .enumerate()
+            .filter(|(_, i)| i.world_level.unwrap_or(0) >= level)
+            .collect::<Vec<(usize, &Entry<U>)>>();
+
+        carries.sort_by_key(|(_, i)| std::cmp::Reverse(entry.shared_targets(i)));
+
+        let mut carries = carries.into_iter().map(|(index, _)| index).take(2).collect::<Vec<usize>>();
+        carries.sort();
+
+        match carries[..] {
+            [first, second] => Some((assist, first, second)),
+            _ => None,
+        }
+    })?;
+
+    let second = queues.queue_b.remove(second);
+    let first = queues.queue_b.remove(first);
+    let assist = queues.queue_c.remove(assist);
+
+    Some(Group {
+        queue_type: true,
+        members: vec![first, second, assist],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use proptest::prelude::*;
+
+    use super::{apply, coop_group, Entry, Event, Group, Queues, Role, TARGET_HOLD};
+
+    const COOP_SIZE: usize = 3;
+
+    fn role() -> impl Strategy<Value = Role> {
+        prop_oneof![Just(Role::Coop), Just(Role::Carry), Just(Role::Assist)]
+    }
+
+    /// Joins and leaves from a small pool of users, so the same users come back often, and ticks
+    /// that sometimes end every hold.
+    fn event() -> impl Strategy<Value = Event<u8>> {
+        let join = (0..12u8, role(), proptest::option::of(1..8i32), proptest::collection::vec(0..3u8, 0..3))
+            .prop_map(|(user, role, world_level, targets)| {
+                let mut entry = Entry::new(user, world_level);
+                entry.targets = targets.into_iter().map(|i| format!("target{}", i)).collect();
+                Event::Join(role, entry)
+            });
+
+        let tick = any::<bool>().prop_map(|late| Event::Tick(Instant::now() + if late { TARGET_HOLD } else { Duration::ZERO }));
+
+        prop_oneof![3 => join, 1 => (0..12u8).prop_map(Event::Leave), 1 => tick]
+    }
+
+    fn users(queues: &Queues<u8>) -> Vec<u8> {
+        [&queues.queue_a, &queues.queue_b, &queues.queue_c].iter()
+            .flat_map(|queue| queue.iter().map(|i| i.user))
+            .collect()
+    }
+
+    /// The queue a grouped user came from: their old queue, or the one they just joined.
+    fn role_of(before: &Queues<u8>, joined: Option<(Role, u8)>, user: u8) -> Option<Role> {
+        [Role::Coop, Role::Carry, Role::Assist].into_iter()
+            .find(|role| before.queue(*role).iter().any(|i| i.user == user))
+            .or(joined.filter(|(_, i)| *i == user).map(|(role, _)| role))
+    }
+
+    fn check_group(before: &Queues<u8>, joined: Option<(Role, u8)>, group: &Group<u8>) {
+        let roles = group.members.iter().map(|i| role_of(before, joined, i.user)).collect::<Vec<Option<Role>>>();
+
+        if group.queue_type {
+            assert_eq!(roles, vec![Some(Role::Carry), Some(Role::Carry), Some(Role::Assist)]);
+
+            let assist = group.members[2].world_level.unwrap_or(0);
+            assert!(group.members[..2].iter().all(|i| i.world_level.unwrap_or(0) >= assist));
+        } else {
+            assert_eq!(roles, vec![Some(Role::Coop); COOP_SIZE]);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn invariants(events in proptest::collection::vec(event(), 0..80)) {
+            let mut queues = Queues::<u8>::default();
+
+            for event in events {
+                let before = queues.clone();
+                let joined = match &event {
+                    Event::Join(role, entry) if !before.contains(&entry.user) => Some((*role, entry.user)),
+                    _ => None,
+                };
+                let left = match &event {
+                    Event::Leave(user) => Some(*user),
+                    _ => None,
+                };
+                let now = match &event {
+                    Event::Join(_, entry) => entry.joined,
+                    Event::Leave(_) => Instant::now(),
+                    Event::Tick(now) => *now,
+                };
+
+                let (after, groups) = apply(queues, COOP_SIZE, event);
+
+                // No duplicates, whether waiting or grouped.
+                let mut waiting = users(&after);
+                waiting.extend(groups.iter().flat_map(|i| i.members.iter().map(|i| i.user)));
+                let mut unique = waiting.clone();
+                unique.sort();
+                unique.dedup();
+                prop_assert_eq!(unique.len(), waiting.len());
+
+                // Nobody is lost: everyone who was waiting, or just joined, is still waiting or grouped,
+                // except the user who left.
+                let mut expected = users(&before);
+                expected.extend(joined.map(|(_, user)| user));
+                expected.retain(|i| Some(*i) != left);
+                expected.sort();
+                prop_assert_eq!(&expected, &unique);
+
+                // Nothing that could be grouped is left waiting.
+                prop_assert!(coop_group(&mut after.queue_a.clone(), COOP_SIZE, now).is_none());
+
+                // A join forms at most one group without holds ending, and the user who joined is part of it.
+                if let (Some((_, user)), Some(group)) = (joined, groups.first()) {
+                    prop_assert!(group.members.iter().any(|i| i.user == user));
+                }
+
+                let mut coop = before.queue_a.iter().map(|i| i.user).collect::<Vec<u8>>();
+                coop.extend(joined.filter(|(role, _)| *role == Role::Coop).map(|(_, user)| user));
+                coop.retain(|i| Some(*i) != left);
+
+                for group in &groups {
+                    check_group(&before, joined, group);
+
+                    if !group.queue_type {
+                        // The longest waiting co-op user is always served first.
+                        prop_assert_eq!(Some(&group.members[0].user), coop.first());
+                        coop.retain(|i| group.members.iter().all(|member| member.user != *i));
+                    } else {
+                        // No earlier assist could have been served with the carries that were waiting.
+                        let assist = group.members[2].user;
+                        if let Some(index) = before.queue_c.iter().position(|i| i.user == assist) {
+                            for earlier in &before.queue_c[..index] {
+                                let level = earlier.world_level.unwrap_or(0);
+                                let eligible = after.queue_b.iter()
+                                    .chain(group.members[..2].iter())
+                                    .filter(|i| i.world_level.unwrap_or(0) >= level)
+                                    .count();
+                                prop_assert!(eligible < 2);
+                            }
+                        }
+                    }
+                }
+
+                queues = after;
+            }
+        }
+
+        #[test]
+        fn coop_without_targets_is_first_come_first_served(users in proptest::collection::vec(0..50u8, 0..30)) {
+            let mut queues = Queues::<u8>::default();
+            let mut order = Vec::new();
+            let mut formed = Vec::new();
+
+            for user in users {
+                if queues.contains(&user) {
+                    continue;
+                }
+                order.push(user);
+
+                let (after, groups) = apply(queues, COOP_SIZE, Event::Join(Role::Coop, Entry::new(user, None)));
+                formed.extend(groups.into_iter().flat_map(|i| i.members.into_iter().map(|i| i.user)));
+                queues = after;
+            }
+
+            formed.extend(queues.queue_a.iter().map(|i| i.user));
+            prop_assert_eq!(formed, order);
+        }
+
+        #[test]
+        fn leaving_removes_only_that_user(users in proptest::collection::vec((0..50u8, role()), 0..30), leaver in 0..50u8) {
+            let mut queues = Queues::<u8>::default();
+            for (user, role) in users {
+                let (after, _) = apply(queues, COOP_SIZE, Event::Join(role, Entry::new(user, None)));
+                queues = after;
+            }
+
+            let before = queues.clone();
+            let (after, groups) = apply(queues, COOP_SIZE, Event::Leave(leaver));
+
+            prop_assert!(groups.is_empty());
+            prop_assert!(!after.contains(&leaver));
+            for role in [Role::Coop, Role::Carry, Role::Assist] {
+                let expected = before.queue(role).iter().map(|i| i.user).filter(|i| *i != leaver).collect::<Vec<u8>>();
+                let remaining = after.queue(role).iter().map(|i| i.user).collect::<Vec<u8>>();
+                prop_assert_eq!(expected, remaining);
+            }
+        }
+    }
+
+    fn coop(user: u8, targets: &[&str]) -> Event<u8> {
+        let mut entry = Entry::new(user, None);
+        entry.targets = targets.iter().map(|i| i.to_string()).collect();
+        Event::Join(Role::Coop, entry)
+    }
+
+    fn members(groups: &[Group<u8>]) -> Vec<u8> {
+        groups.iter().flat_map(|i| i.members.iter().map(|i| i.user)).collect()
+    }
+
+    #[test]
+    fn coop_waits_briefly_for_shared_targets() {
+        let mut queues = Queues::<u8>::default();
+        for event in [coop(1, &["Crownless"]), coop(2, &[]), coop(3, &["Dreamless"]), coop(4, &["Crownless"])] {
+            let (after, groups) = apply(queues, COOP_SIZE, event);
+            assert!(groups.is_empty());
+            queues = after;
+        }
+
+        let (queues, groups) = apply(queues, COOP_SIZE, coop(5, &["Crownless", "Dreamless"]));
+        assert_eq!(members(&groups), vec![1, 4, 5]);
+        assert_eq!(users(&queues), vec![2, 3]);
+    }
+
+    #[test]
+    fn held_coop_groups_form_once_the_hold_runs_out() {
+        let mut queues = Queues::<u8>::default();
+        for event in [coop(1, &["Crownless"]), coop(2, &[]), coop(3, &["Dreamless"])] {
+            let (after, _) = apply(queues, COOP_SIZE, event);
+            queues = after;
+        }
+
+        let joined = queues.queue_a[0].joined;
+        let (queues, groups) = apply(queues, COOP_SIZE, Event::Tick(joined + TARGET_HOLD / 2));
+        assert!(groups.is_empty());
+
+        let (queues, groups) = apply(queues, COOP_SIZE, Event::Tick(joined + TARGET_HOLD));
+        assert_eq!(members(&groups), vec![1, 2, 3]);
+        assert!(queues.is_empty());
+    }
+
+    #[test]
+    fn carries_need_a_high_enough_world_level() {
+        let queues = Queues::<u8>::default();
+        let (queues, _) = apply(queues, COOP_SIZE, Event::Join(Role::Assist, Entry::new(1, Some(6))));
+        let (queues, _) = apply(queues, COOP_SIZE, Event::Join(Role::Carry, Entry::new(2, Some(5))));
+        let (queues, groups) = apply(queues, COOP_SIZE, Event::Join(Role::Carry, Entry::new(3, Some(7))));
+        assert!(groups.is_empty());
+
+        let (queues, groups) = apply(queues, COOP_SIZE, Event::Join(Role::Carry, Entry::new(4, Some(6))));
+        assert_eq!(members(&groups), vec![3, 4, 1]);
+        assert_eq!(queues.queue_b.iter().map(|i| i.user).collect::<Vec<u8>>(), vec![2]);
+    }
+
+    #[test]
+    fn restored_replacements_keep_their_place() {
+        let start = Instant::now();
+        let mut queues = Queues::<u8>::default();
+        for user in 1..=3u8 {
+            let mut entry = Entry::new(user, Some(i32::from(user)));
+            entry.joined = start + Duration::from_secs(u64::from(user));
+            queues.restore(Role::Carry, entry);
+        }
+
+        let taken = queues.take_replacement(true, 0, |level| level >= 2).expect("a carry is eligible");
+        assert_eq!(taken.user, 2);
+
+        queues.restore(Role::Carry, taken);
+        assert_eq!(users(&queues), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,83 @@
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::message::{MessageFlags, embed::Embed},
+    guild::Permissions,
+    http::interaction::{InteractionResponse, InteractionResponseType},
+};
+use twilight_util::builder::{
+    InteractionResponseDataBuilder,
+    embed::EmbedBuilder,
+};
+
+use crate::Bot;
+use crate::error::Result;
+
+/// Who may use a command or component.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+    Everyone,
+    Moderator,
+    Administrator,
+}
+
+pub fn is_admin(interaction: &Interaction) -> bool {
+    interaction.member.as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.contains(Permissions::ADMINISTRATOR))
+}
+
+pub fn denied_embed() -> Embed {
+    EmbedBuilder::new()
+        .color(0xEE4B2B)
+        .title("Permission denied")
+        .description("You need a moderator role to do this.")
+        .build()
+}
+
+impl Bot {
+    /// Administrators, and members holding one of the guild's configured moderator roles.
+    pub async fn is_moderator(&self, interaction: &Interaction) -> Result<bool> {
+        if is_admin(interaction) {
+            return Ok(true);
+        }
+
+        let (Some(guild_id), Some(member)) = (interaction.guild_id, interaction.member.as_ref()) else {
+            return Ok(false);
+        };
+
+        let roles = self.storage.get_mod_roles(guild_id).await?;
+
+        Ok(member.roles.iter().any(|role| roles.contains(role)))
+    }
+
+    pub async fn has_permission(&self, interaction: &Interaction, permission: Permission) -> Result<bool> {
+        match permission {
+            Permission::Everyone => Ok(true),
+            Permission::Moderator => self.is_moderator(interaction).await,
+            Permission::Administrator => Ok(is_admin(interaction)),
+        }
+    }
+
+    /// Replies with the denial embed, as a followup if the interaction was already deferred.
+    pub async fn deny(&self, interaction: &Interaction, deferred: bool) -> Result<()> {
+        let client = self.client.interaction(interaction.application_id);
+
+        if deferred {
+            client.create_followup(&interaction.token).embeds(&[denied_embed()])?.await?;
+        } else {
+            let data = InteractionResponseDataBuilder::new()
+                .embeds([denied_embed()])
+                .flags(MessageFlags::EPHEMERAL)
+                .build();
+
+            let response = InteractionResponse {
+                kind: InteractionResponseType::ChannelMessageWithSource,
+                data: Some(data),
+            };
+
+            client.create_response(interaction.id, &interaction.token, &response).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+//! Keeps tracked session threads in line with Discord. Catches up on thread events missed while
+//! the bot was offline, and clears out sessions that can no longer end on their own.
+
+use std::{env, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use twilight_http::error::ErrorType;
+use twilight_model::{
+    channel::Channel,
+    id::{Id, marker::ChannelMarker},
+};
+use twilight_util::builder::embed::EmbedBuilder;
+
+use crate::Bot;
+use crate::error::Result;
+use crate::interactions::{leave::abandon_penalty, rep::log_channel};
+use crate::scheduler::{JobFuture, JobHandler};
+use crate::storage::{Job, Session};
+
+/// Milliseconds from the Unix epoch to the first Discord snowflake.
+const DISCORD_EPOCH: u64 = 1_420_070_400_000;
+
+/// What to do with a session thread that can't end normally.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Award rep and send the report polls, as if the members had ended it.
+    Finalize,
+    /// Count everyone still in it as having abandoned it, with the usual penalty.
+    Abandon,
+    /// Forget the session without rep or penalties.
+    Delete,
+    /// Leave it alone.
+    Keep,
+}
+
+impl Action {
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "finalize" => Some(Action::Finalize),
+            "abandon" => Some(Action::Abandon),
+            "delete" => Some(Action::Delete),
+            "keep" => Some(Action::Keep),
+            _ => None,
+        }
+    }
+
+    fn past_tense(&self) -> &'static str {
+        match self {
+            Action::Finalize => "finalized",
+            Action::Abandon => "abandoned",
+            Action::Delete => "deleted",
+            Action::Keep => "kept",
+        }
+    }
+}
+
+/// How reconciliation treats each kind of stale session.
+#[derive(Clone, Debug)]
+pub struct Rules {
+    /// Threads Discord no longer has. These can't be finalized, as the thread name is gone with them.
+    pub deleted: Action,
+    /// Threads that were archived without the bot seeing it.
+    pub archived: Action,
+    /// Open threads nobody has written in for `stale_after`.
+    pub stale: Action,
+    pub stale_after: Duration,
+    /// How often to reconcile after the pass at startup, if at all. Runs as a scheduled job, so
+    /// only one instance reconciles at a time.
+    pub interval: Option<Duration>,
+}
+
+impl Rules {
+    /// `RECONCILE_DELETED`, `RECONCILE_ARCHIVED` and `RECONCILE_STALE` take `finalize`, `abandon`,
+    /// `delete` or `keep`. `RECONCILE_STALE_HOURS` and `RECONCILE_INTERVAL_MINUTES` set the timings,
+    /// where an interval of `0` only reconciles at startup.
+    pub fn from_env() -> Self {
+        let action = |name: &str, default: Action| env::var(name).ok()
+            .and_then(|i| Action::from_value(&i.to_lowercase()))
+            .unwrap_or(default);
+        let number = |name: &str, default: u64| env::var(name).ok()
+            .and_then(|i| i.parse::<u64>().ok())
+            .unwrap_or(default);
+
+        let deleted = match action("RECONCILE_DELETED", Action::Delete) {
+            Action::Finalize => {
+                tracing::warn!("deleted threads can't be finalized, deleting them instead");
+                Action::Delete
+            },
+            deleted => deleted,
+        };
+
+        Self {
+            deleted,
+            archived: action("RECONCILE_ARCHIVED", Action::Finalize),
+            stale: action("RECONCILE_STALE", Action::Keep),
+            stale_after: Duration::from_secs(number("RECONCILE_STALE_HOURS", 24) * 60 * 60),
+            interval: Some(number("RECONCILE_INTERVAL_MINUTES", 60))
+                .filter(|i| *i > 0)
+                .map(|i| Duration::from_secs(i * 60)),
+        }
+    }
+}
+
+/// The scheduled pass. It takes its rules from the environment, as the job stores nothing else.
+pub struct Reconcile;
+
+impl JobHandler for Reconcile {
+    const KIND: &'static str = "reconcile";
+
+    fn run<'a>(_job: &'a Job, bot: &'a Bot) -> JobFuture<'a> {
+        Box::pin(async move { bot.reconcile(&Rules::from_env()).await })
+    }
+}
+
+/// How long since the last message in a thread, or since it was created if nobody wrote in it.
+fn idle(channel: &Channel) -> Duration {
+    let id = channel.last_message_id.map_or(channel.id.get(), |i| i.get());
+    let at = UNIX_EPOCH + Duration::from_millis((id >> 22) + DISCORD_EPOCH);
+
+    SystemTime::now().duration_since(at).unwrap_or_default()
+}
+
+impl Bot {
+    /// Checks every tracked session thread against Discord, applies `rules` to those that can't
+    /// end normally and reports anything that was done to the log channel.
+    pub async fn reconcile(&self, rules: &Rules) -> Result<()> {
+        let threads = self.storage.get_threads().await?;
+
+        tracing::info!(threads = threads.len(), "reconciling session threads");
+
+        let mut lines = Vec::new();
+        for thread in threads {
+            match self.reconcile_thread(thread, rules).await {
+                Ok(Some(line)) => lines.push(line),
+                Ok(None) => {},
+                Err(error) => {
+                    tracing::warn!(?error, thread = thread.get(), "failed to reconcile thread");
+                    lines.push(format!("<#{}> could not be checked: {}", thread.get(), error));
+                },
+            }
+        }
+
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        self.report(&lines).await
+    }
+
+    /// Returns a line for the report if anything was done.
+    async fn reconcile_thread(&self, thread: Id<ChannelMarker>, rules: &Rules) -> Result<Option<String>> {
+        let Some(session) = self.storage.get_session(thread).await? else {
+            return Ok(None);
+        };
+
+        let channel = self.fetch_thread(thread).await?;
+        let archived = channel.as_ref()
+            .and_then(|i| i.thread_metadata.as_ref())
+            .is_some_and(|i| i.archived);
+
+        let (state, action) = match &channel {
+            None => ("was deleted", rules.deleted),
+            Some(_) if archived => ("was archived", rules.archived),
+            Some(channel) if idle(channel) >= rules.stale_after => ("went quiet", rules.stale),
+            Some(_) => return self.reconcile_members(thread, &session).await,
+        };
+
+        match (action, channel) {
+            (Action::Keep, Some(_)) if !archived => return self.reconcile_members(thread, &session).await,
+            (Action::Keep, _) => return Ok(None),
+            (Action::Finalize, Some(channel)) => {
+                if !archived {
+                    self.client.update_thread(thread)
+                        .archived(true)
+                        .locked(true)
+                        .await?;
+                }
+
+                self.finish_session(channel).await?;
+            },
+            (Action::Abandon, channel) => {
+                let penalty = abandon_penalty();
+                for user in session.users.into_iter().flatten() {
+                    self.storage.abandon_thread(thread, user, penalty).await?;
+                }
+
+                self.close_session(thread, channel.is_some() && !archived).await?;
+            },
+            (Action::Delete, channel) | (Action::Finalize, channel @ None) => {
+                self.close_session(thread, channel.is_some() && !archived).await?;
+            },
+        }
+
+        Ok(Some(format!("<#{}> {}, so it was {}.", thread.get(), state, action.past_tense())))
+    }
+
+    /// The thread as Discord has it now, or `None` if it was deleted.
+    async fn fetch_thread(&self, thread: Id<ChannelMarker>) -> Result<Option<Channel>> {
+        match self.client.channel(thread).await {
+            Ok(response) => Ok(Some(response.model().await?)),
+            Err(error) if matches!(error.kind(), ErrorType::Response { status, .. } if status.get() == 404) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Members who left an open thread while the bot wasn't listening abandon it, as if it had seen them go.
+    async fn reconcile_members(&self, thread: Id<ChannelMarker>, session: &Session) -> Result<Option<String>> {
+        let members = self.client.thread_members(thread).await?.models().await?;
+
+        let mut left = Vec::new();
+        for user in session.users.iter().flatten() {
+            if !members.iter().any(|i| i.user_id == Some(*user)) {
+                self.abandon_session(thread, *user).await?;
+                left.push(format!("<@{}>", user.get()));
+            }
+        }
+
+        if left.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(format!("<#{}>: {} left while the bot was offline, so they abandoned it.", thread.get(), left.join(" "))))
+    }
+
+    /// Stops tracking a session, archiving its thread if it is still open so nobody keeps using it.
+    async fn close_session(&self, thread: Id<ChannelMarker>, open: bool) -> Result<()> {
+        if self.storage.remove_thread(thread).await? && open {
+            self.client.update_thread(thread)
+                .archived(true)
+                .locked(true)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn report(&self, lines: &[String]) -> Result<()> {
+        // Stays under the embed description limit, however many threads there are.
+        let mut description = String::new();
+        for (index, line) in lines.iter().enumerate() {
+            if description.len() + line.len() > 3900 {
+                description.push_str(&format!("...and {} more.", lines.len() - index));
+                break;
+            }
+
+            description.push_str(line);
+            description.push('\n');
+        }
+
+        let embed = EmbedBuilder::new()
+            .color(0x63c5da)
+            .title("Thread reconciliation")
+            .description(description)
+            .build();
+
+        self.client.create_message(log_channel()?)
+            .embeds(&[embed])?
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use futures_util::future::BoxFuture;
+use twilight_interactions::command::CreateCommand;
+use twilight_model::{
+    application::{
+        command::Command,
+        interaction::{
+            application_command::CommandData,
+            message_component::MessageComponentInteractionData,
+            modal::ModalInteractionData,
+            Interaction,
+        },
+    },
+    channel::message::component::ComponentType,
+};
+
+use crate::Bot;
+use crate::custom_id::{CustomId, DecodeError};
+use crate::error::{BotError, Result};
+use crate::interactions::{
+    config::ConfigCommand,
+    end::End,
+    jobs::JobsCommand,
+    leave::Leave,
+    ping::Ping,
+    profile::ProfileCommand,
+    queue::Queue,
+    queue_command::QueueCommand,
+    rep::Report,
+    rep_command::RepCommand,
+    requeue::Requeue,
+    setup::Setup,
+    targets::Targets,
+};
+use crate::permissions::Permission;
+
+pub type HandlerFuture<'a> = BoxFuture<'a, Result<()>>;
+
+/// A slash command, registered with Discord and dispatched by name.
+pub trait SlashCommand: CreateCommand {
+    /// The name the command is invoked with.
+    const NAME: &'static str;
+    const PERMISSION: Permission = Permission::Everyone;
+
+    fn handle(interaction: Interaction, data: CommandData, bot: &Bot) -> HandlerFuture<'_>;
+}
+
+/// A button, select menu or modal, dispatched by the tag of its custom id.
+pub trait ComponentHandler {
+    /// The `CustomId` tags this handles.
+    const TAGS: &'static [&'static str];
+    const PERMISSION: Permission = Permission::Everyone;
+
+    fn handle(interaction: Interaction, data: ComponentData, id: CustomId, bot: &Bot) -> HandlerFuture<'_>;
+}
+
+/// What was submitted with a component interaction.
+pub enum ComponentData {
+    Message(MessageComponentInteractionData),
+    Modal(ModalInteractionData),
+}
+
+impl ComponentData {
+    pub fn custom_id(&self) -> &str {
+        match self {
+            ComponentData::Message(data) => &data.custom_id,
+            ComponentData::Modal(data) => &data.custom_id,
+        }
+    }
+
+    /// The kind of message component, or `None` for a modal.
+    pub fn component_type(&self) -> Option<ComponentType> {
+        match self {
+            ComponentData::Message(data) => Some(data.component_type),
+            ComponentData::Modal(_) => None,
+        }
+    }
+
+    /// Values chosen in a select menu.
+    pub fn values(&self) -> &[String] {
+        match self {
+            ComponentData::Message(data) => data.values.as_slice(),
+            ComponentData::Modal(_) => &[],
+        }
+    }
+}
+
+struct CommandEntry {
+    command: Command,
+    permission: Permission,
+    handler: fn(Interaction, CommandData, &Bot) -> HandlerFuture<'_>,
+}
+
+struct ComponentEntry {
+    permission: Permission,
+    handler: fn(Interaction, ComponentData, CustomId, &Bot) -> HandlerFuture<'_>,
+}
+
+/// Every command and component the bot handles.
+pub struct Registry {
+    commands: HashMap<&'static str, CommandEntry>,
+    components: HashMap<&'static str, ComponentEntry>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            commands: HashMap::new(),
+            components: HashMap::new(),
+        };
+
+        registry.command::<Ping>();
+        registry.command::<Setup>();
+        registry.command::<ConfigCommand>();
+        registry.command::<End>();
+        registry.command::<Leave>();
+        registry.command::<QueueCommand>();
+        registry.command::<RepCommand>();
+        registry.command::<ProfileCommand>();
+        registry.command::<JobsCommand>();
+
+        registry.component::<Queue>();
+        registry.component::<End>();
+        registry.component::<Leave>();
+        registry.component::<Requeue>();
+        registry.component::<Targets>();
+        registry.component::<Report>();
+
+        registry
+    }
+
+    fn command<T: SlashCommand>(&mut self) {
+        self.commands.insert(<T as SlashCommand>::NAME, CommandEntry {
+            command: T::create_command().into(),
+            permission: T::PERMISSION,
+            handler: T::handle,
+        });
+    }
+
+    fn component<T: ComponentHandler>(&mut self) {
+        for tag in T::TAGS {
+            self.components.insert(*tag, ComponentEntry {
+                permission: T::PERMISSION,
+                handler: T::handle,
+            });
+        }
+    }
+
+    /// Definitions of every command, for registering them globally.
+    pub fn commands(&self) -> Vec<Command> {
+        self.commands.values().map(|i| i.command.clone()).collect()
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bot {
+    pub async fn dispatch_command(&self, interaction: Interaction, data: CommandData) -> Result<()> {
+        let Some(entry) = self.registry.commands.get(&*data.name) else {
+            return Err(BotError::User(format!("The command `{}` is not available anymore.", data.name)));
+        };
+
+        if !self.has_permission(&interaction, entry.permission).await? {
+            return Err(BotError::PermissionDenied);
+        }
+
+        (entry.handler)(interaction, data, self).await
+    }
+
+    pub async fn dispatch_component(&self, interaction: Interaction, data: ComponentData) -> Result<()> {
+        let id = CustomId::decode(data.custom_id())?;
+
+        let entry = self.registry.components.get(id.tag()).ok_or(DecodeError::Malformed)?;
+
+        if !self.has_permission(&interaction, entry.permission).await? {
+            return Err(BotError::PermissionDenied);
+        }
+
+        (entry.handler)(interaction, data, id, self).await
+    }
+}
@@ -0,0 +1,129 @@
+//! Runs jobs stored alongside everything else, so they survive restarts. Due jobs are leased to
+//! one instance before they run, so several instances sharing a database never run a job twice.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures_util::future::BoxFuture;
+use rand::distributions::{Alphanumeric, DistString};
+
+use crate::Bot;
+use crate::error::Result;
+use crate::reconcile::Reconcile;
+use crate::storage::Job;
+
+/// How long a job stays with the instance that claimed it. One that runs longer may be picked up
+/// again by another instance, and one whose instance died is retried once this runs out.
+pub const JOB_LEASE: Duration = Duration::from_secs(10 * 60);
+/// How often to look for due jobs.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// How long a failed one-shot job waits before it is tried again.
+const RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+pub type JobFuture<'a> = BoxFuture<'a, Result<()>>;
+
+/// Work the scheduler can run, dispatched by the kind stored with each job.
+pub trait JobHandler {
+    /// The kind of job this runs.
+    const KIND: &'static str;
+
+    fn run<'a>(job: &'a Job, bot: &'a Bot) -> JobFuture<'a>;
+}
+
+/// Every kind of job this instance can run, and the name it leases jobs under.
+pub struct Scheduler {
+    owner: String,
+    handlers: HashMap<&'static str, for<'a> fn(&'a Job, &'a Bot) -> JobFuture<'a>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        let mut scheduler = Self {
+            owner: Alphanumeric.sample_string(&mut rand::thread_rng(), 16),
+            handlers: HashMap::new(),
+        };
+
+        scheduler.handler::<Reconcile>();
+
+        scheduler
+    }
+
+    fn handler<T: JobHandler>(&mut self) {
+        self.handlers.insert(T::KIND, T::run);
+    }
+
+    /// Identifies this instance in job leases.
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The current time in Unix seconds, as jobs are stored.
+pub fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64
+}
+
+impl Bot {
+    /// Runs due jobs every few seconds until the task is dropped.
+    pub async fn run_scheduler(&self) {
+        loop {
+            if let Err(error) = self.run_due_jobs().await {
+                tracing::error!(?error, "failed to run scheduled jobs");
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Claims every due job and runs it. Returns how many ran.
+    pub async fn run_due_jobs(&self) -> Result<usize> {
+        let owner = self.scheduler.owner();
+        let jobs = self.storage.claim_jobs(owner, unix_now(), JOB_LEASE.as_secs() as i64).await?;
+
+        for job in &jobs {
+            let result = match self.scheduler.handlers.get(job.kind.as_str()) {
+                Some(handler) => handler(job, self).await.map_err(|error| error.to_string()),
+                None => Err(format!("no handler for jobs of kind {}", job.kind)),
+            };
+
+            if let Err(error) = &result {
+                tracing::warn!(%error, id = job.id, name = %job.name, "scheduled job failed");
+            }
+
+            // Recurring jobs move on whether or not they failed, and one-shot jobs are only done once they succeed.
+            let next_run = match (job.interval, &result) {
+                (Some(interval), _) => Some(unix_now() + interval),
+                (None, Ok(())) => None,
+                (None, Err(_)) => Some(unix_now() + RETRY_AFTER.as_secs() as i64),
+            };
+
+            // An unfinished job is claimed again once its lease runs out, so the rest still get finished.
+            if let Err(error) = self.storage.finish_job(job.id, owner, next_run, result.err().as_deref()).await {
+                tracing::error!(?error, id = job.id, name = %job.name, "failed to finish scheduled job");
+            }
+        }
+
+        Ok(jobs.len())
+    }
+
+    /// Runs a job once at `at`. Scheduling it again under the same name moves it.
+    pub async fn schedule_once(&self, name: &str, kind: &str, payload: &str, at: SystemTime) -> Result<()> {
+        let at = at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+
+        self.storage.schedule_job(name, kind, payload, at, None).await
+    }
+
+    /// Runs a job every `interval`, first after one interval has passed. Scheduling it again under
+    /// the same name changes the interval from the next run on.
+    pub async fn schedule_every(&self, name: &str, kind: &str, payload: &str, interval: Duration) -> Result<()> {
+        let interval = interval.as_secs().max(1) as i64;
+
+        self.storage.schedule_job(name, kind, payload, unix_now() + interval, Some(interval)).await
+    }
+}
@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::{Mutex, MutexGuard};
+use twilight_model::id::{
+        Id,
+        marker::{
+            UserMarker,
+            ChannelMarker,
+            GuildMarker,
+            MessageMarker,
+            RoleMarker,
+        }
+    };
+
+use crate::interactions::board::BoardKind;
+use super::{Board, GatewaySession, Job, Profile, ReportDraft, SavedEntry, Session, Storage, StorageFuture, REPORT_DRAFT_TTL};
+
+struct Thread {
+    board: Option<Id<MessageMarker>>,
+    size: usize,
+    users: [Option<Id<UserMarker>>; 3],
+    end_request: Option<(String, Id<UserMarker>)>,
+}
+
+struct StoredBoard {
+    message_id: Id<MessageMarker>,
+    channel_id: Id<ChannelMarker>,
+    guild_id: Id<GuildMarker>,
+    kind: BoardKind,
+    paused: bool,
+}
+
+/// User, moderator, action, old rep, new rep and reason.
+type AuditEntry = (Id<UserMarker>, Id<UserMarker>, String, i32, i32, String);
+
+#[derive(Default)]
+struct State {
+    users: HashMap<Id<UserMarker>, i32>,
+    rep_audit: Vec<AuditEntry>,
+    profiles: HashMap<Id<UserMarker>, Profile>,
+    threads: HashMap<Id<ChannelMarker>, Thread>,
+    abandons: Vec<(Id<ChannelMarker>, Id<UserMarker>, i32)>,
+    report_drafts: HashMap<String, (ReportDraft, i64)>,
+    /// In creation order, so the last matching board is the newest.
+    boards: Vec<StoredBoard>,
+    mod_roles: HashMap<Id<GuildMarker>, Vec<Id<RoleMarker>>>,
+    saved_queues: Vec<SavedEntry>,
+    gateway_sessions: Vec<GatewaySession>,
+    /// Each job with the instance leasing it.
+    jobs: Vec<(Job, Option<String>)>,
+    next_job_id: i64,
+}
+
+/// Keeps everything in memory and loses it on restart. Used by tests.
+#[derive(Default)]
+pub struct MemoryStorage {
+    state: Mutex<State>,
+    latency: Duration,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits `latency` before every operation, like a round trip to a remote database.
+    pub fn with_latency(latency: Duration) -> Self {
+        Self {
+            latency,
+            ..Self::default()
+        }
+    }
+
+    /// The wait happens before locking, so concurrent operations overlap as they would on a real pool.
+    async fn state(&self) -> MutexGuard<'_, State> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+
+        self.state.lock().await
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |i| i.as_secs() as i64)
+}
+
+impl Storage for MemoryStorage {
+    fn setup(&self) -> StorageFuture<'_, ()> {
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn update_user(&self, user_id: Id<UserMarker>, change: i32) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            *self.state().await.users.entry(user_id).or_insert(0) += change;
+
+            Ok(())
+        })
+    }
+
+    fn adjust_rep<'a>(&'a self, user_id: Id<UserMarker>, moderator: Id<UserMarker>, action: &'a str, rep: Box<dyn FnOnce(i32) -> i32 + Send + 'a>, reason: &'a str) -> StorageFuture<'a, (i32, i32)> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+
+            let current = state.users.entry(user_id).or_insert(0);
+            let old = *current;
+            let new = rep(old);
+            *current = new;
+
+            state.rep_audit.push((user_id, moderator, action.to_owned(), old, new, reason.to_owned()));
+
+            Ok((old, new))
+        })
+    }
+
+    fn get_rep(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, Option<i32>> {
+        Box::pin(async move { Ok(self.state().await.users.get(&user_id).copied()) })
+    }
+
+    fn set_profile<'a>(&'a self, user_id: Id<UserMarker>, profile: &'a Profile) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            self.state().await.profiles.insert(user_id, profile.clone());
+
+            Ok(())
+        })
+    }
+
+    fn get_profile(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, Option<Profile>> {
+        Box::pin(async move { Ok(self.state().await.profiles.get(&user_id).cloned()) })
+    }
+
+    fn insert_thread<'a>(&'a self, channel_id: Id<ChannelMarker>, board: Id<MessageMarker>, users: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            self.state().await.threads.entry(channel_id).or_insert_with(|| Thread {
+                board: Some(board),
+                size: users.len(),
+                users: [users.first().copied(), users.get(1).copied(), users.get(2).copied()],
+                end_request: None,
+            });
+
+            Ok(())
+        })
+    }
+
+    fn is_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move { Ok(self.state().await.threads.contains_key(&channel_id)) })
+    }
+
+    fn in_thread(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(self.state().await.threads.values().any(|i| i.users.contains(&Some(user_id))))
+        })
+    }
+
+    fn get_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<Vec<Id<UserMarker>>>> {
+        Box::pin(async move {
+            Ok(self.state().await.threads.get(&channel_id)
+                .map(|i| i.users.iter().flatten().copied().collect()))
+        })
+    }
+
+    fn get_session(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<Session>> {
+        Box::pin(async move {
+            Ok(self.state().await.threads.get(&channel_id).map(|i| Session {
+                board: i.board,
+                users: i.users.iter().take(i.size).copied().collect(),
+            }))
+        })
+    }
+
+    fn abandon_thread(&self, channel_id: Id<ChannelMarker>, user_id: Id<UserMarker>, penalty: i32) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+
+            let Some(thread) = state.threads.get_mut(&channel_id).filter(|i| i.users.contains(&Some(user_id))) else {
+                return Ok(false);
+            };
+
+            for slot in thread.users.iter_mut().filter(|i| **i == Some(user_id)) {
+                *slot = None;
+            }
+
+            state.abandons.push((channel_id, user_id, penalty));
+
+            if penalty != 0 {
+                *state.users.entry(user_id).or_insert(0) -= penalty;
+            }
+
+            Ok(true)
+        })
+    }
+
+    fn fill_thread(&self, channel_id: Id<ChannelMarker>, slot: i32, user_id: Id<UserMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+
+            let Some(thread) = state.threads.get_mut(&channel_id) else {
+                return Ok(false);
+            };
+
+            match usize::try_from(slot).ok().and_then(|i| thread.users.get_mut(i)) {
+                Some(user @ None) => {
+                    *user = Some(user_id);
+                    Ok(true)
+                },
+                _ => Ok(false),
+            }
+        })
+    }
+
+    fn set_end_request<'a>(&'a self, channel_id: Id<ChannelMarker>, request: &'a str, user_id: Id<UserMarker>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            if let Some(thread) = self.state().await.threads.get_mut(&channel_id) {
+                thread.end_request = Some((request.to_owned(), user_id));
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_end_request(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<(String, Id<UserMarker>)>> {
+        Box::pin(async move {
+            Ok(self.state().await.threads.get(&channel_id).and_then(|i| i.end_request.clone()))
+        })
+    }
+
+    fn remove_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move { Ok(self.state().await.threads.remove(&channel_id).is_some()) })
+    }
+
+    fn finish_thread<'a>(&'a self, channel_id: Id<ChannelMarker>, rep: &'a [(Id<UserMarker>, i32)]) -> StorageFuture<'a, bool> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+            if state.threads.remove(&channel_id).is_none() {
+                return Ok(false);
+            }
+
+            for (user_id, change) in rep {
+                *state.users.entry(*user_id).or_insert(0) += change;
+            }
+
+            Ok(true)
+        })
+    }
+
+    fn get_threads(&self) -> StorageFuture<'_, Vec<Id<ChannelMarker>>> {
+        Box::pin(async move { Ok(self.state().await.threads.keys().copied().collect()) })
+    }
+
+    fn insert_report_draft<'a>(&'a self, token: &'a str, reporter: Id<UserMarker>, session: Id<ChannelMarker>, remaining: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+            let now = now();
+
+            state.report_drafts.retain(|_, (_, created)| *created >= now - REPORT_DRAFT_TTL);
+            state.report_drafts.insert(token.to_owned(), (ReportDraft {
+                reporter,
+                session,
+                message: None,
+                remaining: remaining.to_vec(),
+                selected: Vec::new(),
+            }, now));
+
+            Ok(())
+        })
+    }
+
+    fn set_report_draft_message<'a>(&'a self, token: &'a str, channel_id: Id<ChannelMarker>, message_id: Id<MessageMarker>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            if let Some((draft, _)) = self.state().await.report_drafts.get_mut(token) {
+                draft.message = Some((channel_id, message_id));
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_report_draft<'a>(&'a self, token: &'a str) -> StorageFuture<'a, Option<ReportDraft>> {
+        Box::pin(async move {
+            Ok(self.state().await.report_drafts.get(token).map(|(draft, _)| draft.clone()))
+        })
+    }
+
+    fn update_report_draft<'a>(&'a self, token: &'a str, remaining: &'a [Id<UserMarker>], selected: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            if let Some((draft, _)) = self.state().await.report_drafts.get_mut(token) {
+                draft.remaining = remaining.to_vec();
+                draft.selected = selected.to_vec();
+            }
+
+            Ok(())
+        })
+    }
+
+    fn claim_report_draft<'a>(&'a self, token: &'a str, selected: &'a [Id<UserMarker>], remaining: &'a [Id<UserMarker>], penalty: i32) -> StorageFuture<'a, bool> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+
+            match state.report_drafts.get_mut(token) {
+                Some((draft, _)) if draft.selected == selected => {
+                    draft.remaining = remaining.to_vec();
+                    draft.selected = Vec::new();
+                },
+                _ => return Ok(false),
+            }
+
+            if penalty != 0 {
+                for user in selected {
+                    *state.users.entry(*user).or_insert(0) -= penalty;
+                }
+            }
+
+            Ok(true)
+        })
+    }
+
+    fn remove_report_draft<'a>(&'a self, token: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            self.state().await.report_drafts.remove(token);
+
+            Ok(())
+        })
+    }
+
+    fn insert_board(&self, message_id: Id<MessageMarker>, channel_id: Id<ChannelMarker>, guild_id: Id<GuildMarker>, kind: BoardKind) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+
+            if !state.boards.iter().any(|i| i.message_id == message_id) {
+                state.boards.push(StoredBoard { message_id, channel_id, guild_id, kind, paused: false });
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_default_board(&self, guild_id: Id<GuildMarker>) -> StorageFuture<'_, Option<(Id<MessageMarker>, Id<ChannelMarker>)>> {
+        Box::pin(async move {
+            Ok(self.state().await.boards.iter().rev()
+                .find(|i| i.guild_id == guild_id)
+                .map(|i| (i.message_id, i.channel_id)))
+        })
+    }
+
+    fn get_channel_board(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<(Id<MessageMarker>, Id<ChannelMarker>)>> {
+        Box::pin(async move {
+            Ok(self.state().await.boards.iter().rev()
+                .find(|i| i.channel_id == channel_id)
+                .map(|i| (i.message_id, i.channel_id)))
+        })
+    }
+
+    fn get_board_channel(&self, message_id: Id<MessageMarker>) -> StorageFuture<'_, Option<Id<ChannelMarker>>> {
+        Box::pin(async move {
+            Ok(self.state().await.boards.iter()
+                .find(|i| i.message_id == message_id)
+                .map(|i| i.channel_id))
+        })
+    }
+
+    fn get_board_kind(&self, message_id: Id<MessageMarker>) -> StorageFuture<'_, BoardKind> {
+        Box::pin(async move {
+            Ok(self.state().await.boards.iter()
+                .find(|i| i.message_id == message_id)
+                .map_or(BoardKind::Elite, |i| i.kind))
+        })
+    }
+
+    fn set_board_paused(&self, message_id: Id<MessageMarker>, paused: bool) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(board) = self.state().await.boards.iter_mut().find(|i| i.message_id == message_id) {
+                board.paused = paused;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_boards(&self) -> StorageFuture<'_, Vec<Board>> {
+        Box::pin(async move {
+            Ok(self.state().await.boards.iter()
+                .map(|i| Board { message_id: i.message_id, kind: i.kind, paused: i.paused })
+                .collect())
+        })
+    }
+
+    fn add_mod_role(&self, guild_id: Id<GuildMarker>, role_id: Id<RoleMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+            let roles = state.mod_roles.entry(guild_id).or_default();
+
+            if roles.contains(&role_id) {
+                return Ok(false);
+            }
+
+            roles.push(role_id);
+            Ok(true)
+        })
+    }
+
+    fn remove_mod_role(&self, guild_id: Id<GuildMarker>, role_id: Id<RoleMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+            let Some(roles) = state.mod_roles.get_mut(&guild_id) else {
+                return Ok(false);
+            };
+
+            let len = roles.len();
+            roles.retain(|i| *i != role_id);
+
+            Ok(roles.len() < len)
+        })
+    }
+
+    fn get_mod_roles(&self, guild_id: Id<GuildMarker>) -> StorageFuture<'_, Vec<Id<RoleMarker>>> {
+        Box::pin(async move {
+            Ok(self.state().await.mod_roles.get(&guild_id).cloned().unwrap_or_default())
+        })
+    }
+
+    fn save_queues<'a>(&'a self, entries: &'a [SavedEntry]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            self.state().await.saved_queues = entries.to_vec();
+
+            Ok(())
+        })
+    }
+
+    fn take_queues(&self) -> StorageFuture<'_, Vec<SavedEntry>> {
+        Box::pin(async move { Ok(std::mem::take(&mut self.state().await.saved_queues)) })
+    }
+
+    fn save_gateway_sessions<'a>(&'a self, sessions: &'a [GatewaySession]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            self.state().await.gateway_sessions = sessions.to_vec();
+
+            Ok(())
+        })
+    }
+
+    fn take_gateway_sessions(&self) -> StorageFuture<'_, Vec<GatewaySession>> {
+        Box::pin(async move { Ok(std::mem::take(&mut self.state().await.gateway_sessions)) })
+    }
+
+    fn schedule_job<'a>(&'a self, name: &'a str, kind: &'a str, payload: &'a str, run_at: i64, interval: Option<i64>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+
+            if let Some((job, _)) = state.jobs.iter_mut().find(|(i, _)| i.name == name) {
+                job.kind = kind.to_owned();
+                job.payload = payload.to_owned();
+                if interval.is_none() {
+                    job.run_at = run_at;
+                }
+                job.interval = interval;
+
+                return Ok(());
+            }
+
+            state.next_job_id += 1;
+            let id = state.next_job_id;
+            state.jobs.push((Job {
+                id,
+                name: name.to_owned(),
+                kind: kind.to_owned(),
+                payload: payload.to_owned(),
+                run_at,
+                interval,
+                lease_until: None,
+                last_error: None,
+            }, None));
+
+            Ok(())
+        })
+    }
+
+    fn claim_jobs<'a>(&'a self, owner: &'a str, now: i64, lease: i64) -> StorageFuture<'a, Vec<Job>> {
+        Box::pin(async move {
+            let mut claimed = Vec::new();
+
+            for (job, lease_owner) in &mut self.state().await.jobs {
+                if job.run_at <= now && job.lease_until.is_none_or(|i| i <= now) {
+                    job.lease_until = Some(now + lease);
+                    *lease_owner = Some(owner.to_owned());
+                    claimed.push(job.clone());
+                }
+            }
+
+            claimed.sort_by_key(|i| i.run_at);
+
+            Ok(claimed)
+        })
+    }
+
+    fn finish_job<'a>(&'a self, id: i64, owner: &'a str, next_run: Option<i64>, error: Option<&'a str>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+            let Some(index) = state.jobs.iter().position(|(job, lease_owner)| job.id == id && lease_owner.as_deref() == Some(owner)) else {
+                return Ok(());
+            };
+
+            match next_run {
+                Some(next_run) => {
+                    let (job, lease_owner) = &mut state.jobs[index];
+                    job.run_at = next_run;
+                    job.lease_until = None;
+                    job.last_error = error.map(|i| i.to_owned());
+                    *lease_owner = None;
+                },
+                None => {
+                    state.jobs.remove(index);
+                },
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_jobs(&self) -> StorageFuture<'_, Vec<Job>> {
+        Box::pin(async move {
+            let mut jobs = self.state().await.jobs.iter().map(|(job, _)| job.clone()).collect::<Vec<Job>>();
+            jobs.sort_by_key(|i| i.run_at);
+
+            Ok(jobs)
+        })
+    }
+
+    fn cancel_job(&self, id: i64) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            let mut state = self.state().await;
+            let len = state.jobs.len();
+            state.jobs.retain(|(job, _)| job.id != id);
+
+            Ok(state.jobs.len() < len)
+        })
+    }
+
+    fn close(&self) -> StorageFuture<'_, ()> {
+        Box::pin(async move { Ok(()) })
+    }
+}
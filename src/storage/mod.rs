@@ -0,0 +1,402 @@
+mod memory;
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use std::time::{Duration, Instant};
+
+use futures_util::future::BoxFuture;
+use twilight_model::id::{
+        Id,
+        marker::{
+            UserMarker,
+            ChannelMarker,
+            GuildMarker,
+            MessageMarker,
+            RoleMarker,
+        }
+    };
+
+pub use memory::MemoryStorage;
+pub use postgres::PostgresStorage;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+
+use crate::{Bot, QueueEntry};
+use crate::error::Result;
+use crate::interactions::board::BoardKind;
+use crate::matchmaking::Role;
+
+pub type StorageFuture<'a, T> = BoxFuture<'a, Result<T>>;
+
+/// Reports older than this are dropped whenever a new one is created.
+const REPORT_DRAFT_TTL: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub uid: String,
+    pub name: String,
+    pub world_level: i32,
+    pub region: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub board: Option<Id<MessageMarker>>,
+    pub users: Vec<Option<Id<UserMarker>>>,
+}
+
+/// A report poll sent to a member after a session, kept until every option has been used.
+#[derive(Clone, Debug)]
+pub struct ReportDraft {
+    pub reporter: Id<UserMarker>,
+    pub session: Id<ChannelMarker>,
+    pub message: Option<(Id<ChannelMarker>, Id<MessageMarker>)>,
+    pub remaining: Vec<Id<UserMarker>>,
+    pub selected: Vec<Id<UserMarker>>,
+}
+
+/// Someone who was still waiting in a queue when the bot shut down.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedEntry {
+    pub board: Id<MessageMarker>,
+    pub role: Role,
+    pub user: Id<UserMarker>,
+    pub world_level: Option<i32>,
+    pub targets: Vec<String>,
+    /// Seconds they had been waiting, so their wait carries over the restart.
+    pub waited: i64,
+}
+
+/// A shard's gateway session, kept over a restart so the shard can resume instead of missing events.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GatewaySession {
+    pub shard: u64,
+    /// How many shards there were. Sessions can only be resumed with the same sharding.
+    pub total: u64,
+    pub session_id: String,
+    pub sequence: u64,
+}
+
+/// Work for the scheduler, stored so it survives restarts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Job {
+    pub id: i64,
+    /// Unique, so scheduling a job under the same name updates it instead of adding another.
+    pub name: String,
+    /// Which handler runs it.
+    pub kind: String,
+    /// Whatever the handler needs, in a format of its choosing.
+    pub payload: String,
+    /// Unix seconds of the next run.
+    pub run_at: i64,
+    /// Seconds between runs, or `None` for a job that runs once.
+    pub interval: Option<i64>,
+    /// Unix seconds until which an instance has claimed the job.
+    pub lease_until: Option<i64>,
+    /// Why the last run failed, if it did.
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Board {
+    pub message_id: Id<MessageMarker>,
+    pub kind: BoardKind,
+    pub paused: bool,
+}
+
+/// Everything the bot persists: reputation, profiles, sessions, report polls, boards and guild config.
+pub trait Storage: Send + Sync {
+    /// Creates or migrates the schema.
+    fn setup(&self) -> StorageFuture<'_, ()>;
+
+    fn update_user(&self, user_id: Id<UserMarker>, change: i32) -> StorageFuture<'_, ()>;
+    /// Applies a moderator's rep change and records it in the audit log, returning the old and new rep.
+    fn adjust_rep<'a>(&'a self, user_id: Id<UserMarker>, moderator: Id<UserMarker>, action: &'a str, rep: Box<dyn FnOnce(i32) -> i32 + Send + 'a>, reason: &'a str) -> StorageFuture<'a, (i32, i32)>;
+    fn get_rep(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, Option<i32>>;
+
+    fn set_profile<'a>(&'a self, user_id: Id<UserMarker>, profile: &'a Profile) -> StorageFuture<'a, ()>;
+    fn get_profile(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, Option<Profile>>;
+
+    /// Records a session. Groups smaller than three leave the unused slots empty.
+    fn insert_thread<'a>(&'a self, channel_id: Id<ChannelMarker>, board: Id<MessageMarker>, users: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()>;
+    /// Whether the channel is an active session thread.
+    fn is_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, bool>;
+    /// Whether the user is a member of an active session.
+    fn in_thread(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, bool>;
+    fn get_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<Vec<Id<UserMarker>>>>;
+    fn get_session(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<Session>>;
+    /// Clears the user's slot in the session, records the abandon and takes `penalty` off their
+    /// rep in one go, returning false without changing anything if they were not a member.
+    fn abandon_thread(&self, channel_id: Id<ChannelMarker>, user_id: Id<UserMarker>, penalty: i32) -> StorageFuture<'_, bool>;
+    /// Fills an empty slot (0-based) of the session with a new user, returning false if the slot
+    /// was taken in the meantime.
+    fn fill_thread(&self, channel_id: Id<ChannelMarker>, slot: i32, user_id: Id<UserMarker>) -> StorageFuture<'_, bool>;
+    fn set_end_request<'a>(&'a self, channel_id: Id<ChannelMarker>, request: &'a str, user_id: Id<UserMarker>) -> StorageFuture<'a, ()>;
+    fn get_end_request(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<(String, Id<UserMarker>)>>;
+    /// Stops tracking a session thread, returning whether it was still tracked.
+    fn remove_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, bool>;
+    /// Stops tracking a session thread and awards its rep in one go, returning false without
+    /// awarding anything if it was no longer tracked.
+    fn finish_thread<'a>(&'a self, channel_id: Id<ChannelMarker>, rep: &'a [(Id<UserMarker>, i32)]) -> StorageFuture<'a, bool>;
+    /// Every session thread that is still open.
+    fn get_threads(&self) -> StorageFuture<'_, Vec<Id<ChannelMarker>>>;
+
+    /// Drafts older than 30 days are dropped whenever a new one is created.
+    fn insert_report_draft<'a>(&'a self, token: &'a str, reporter: Id<UserMarker>, session: Id<ChannelMarker>, remaining: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()>;
+    fn set_report_draft_message<'a>(&'a self, token: &'a str, channel_id: Id<ChannelMarker>, message_id: Id<MessageMarker>) -> StorageFuture<'a, ()>;
+    fn get_report_draft<'a>(&'a self, token: &'a str) -> StorageFuture<'a, Option<ReportDraft>>;
+    fn update_report_draft<'a>(&'a self, token: &'a str, remaining: &'a [Id<UserMarker>], selected: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()>;
+    /// Clears the selection, sets who is left on the poll and takes `penalty` off each selected
+    /// user's rep, but only if `selected` is still the selection. Returns whether it was, so each
+    /// selection is reported and penalised once.
+    fn claim_report_draft<'a>(&'a self, token: &'a str, selected: &'a [Id<UserMarker>], remaining: &'a [Id<UserMarker>], penalty: i32) -> StorageFuture<'a, bool>;
+    fn remove_report_draft<'a>(&'a self, token: &'a str) -> StorageFuture<'a, ()>;
+
+    fn insert_board(&self, message_id: Id<MessageMarker>, channel_id: Id<ChannelMarker>, guild_id: Id<GuildMarker>, kind: BoardKind) -> StorageFuture<'_, ()>;
+    /// The most recently created board in the guild.
+    fn get_default_board(&self, guild_id: Id<GuildMarker>) -> StorageFuture<'_, Option<(Id<MessageMarker>, Id<ChannelMarker>)>>;
+    /// The most recently created board in the channel.
+    fn get_channel_board(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<(Id<MessageMarker>, Id<ChannelMarker>)>>;
+    fn get_board_channel(&self, message_id: Id<MessageMarker>) -> StorageFuture<'_, Option<Id<ChannelMarker>>>;
+    /// The kind of a board, treating unknown boards as elite boards.
+    fn get_board_kind(&self, message_id: Id<MessageMarker>) -> StorageFuture<'_, BoardKind>;
+    fn set_board_paused(&self, message_id: Id<MessageMarker>, paused: bool) -> StorageFuture<'_, ()>;
+    fn get_boards(&self) -> StorageFuture<'_, Vec<Board>>;
+
+    fn add_mod_role(&self, guild_id: Id<GuildMarker>, role_id: Id<RoleMarker>) -> StorageFuture<'_, bool>;
+    fn remove_mod_role(&self, guild_id: Id<GuildMarker>, role_id: Id<RoleMarker>) -> StorageFuture<'_, bool>;
+    fn get_mod_roles(&self, guild_id: Id<GuildMarker>) -> StorageFuture<'_, Vec<Id<RoleMarker>>>;
+
+    /// Replaces any saved queues with `entries`, keeping their order.
+    fn save_queues<'a>(&'a self, entries: &'a [SavedEntry]) -> StorageFuture<'a, ()>;
+    /// The saved queues in the order they were saved. They are removed so they are only restored once.
+    fn take_queues(&self) -> StorageFuture<'_, Vec<SavedEntry>>;
+
+    /// Replaces any saved gateway sessions with `sessions`.
+    fn save_gateway_sessions<'a>(&'a self, sessions: &'a [GatewaySession]) -> StorageFuture<'a, ()>;
+    /// The saved gateway sessions, removed so a session is only resumed once.
+    fn take_gateway_sessions(&self) -> StorageFuture<'_, Vec<GatewaySession>>;
+
+    /// Adds a job, or updates the job with the same name. A recurring job keeps its next run,
+    /// so restarting the bot doesn't keep postponing it.
+    fn schedule_job<'a>(&'a self, name: &'a str, kind: &'a str, payload: &'a str, run_at: i64, interval: Option<i64>) -> StorageFuture<'a, ()>;
+    /// Leases every job due at `now` to `owner` for `lease` seconds and returns them. Jobs leased to
+    /// another instance are skipped until the lease runs out, so no job runs twice at once.
+    fn claim_jobs<'a>(&'a self, owner: &'a str, now: i64, lease: i64) -> StorageFuture<'a, Vec<Job>>;
+    /// Releases a job leased to `owner`, moving it to `next_run`, or removing it if there is none.
+    fn finish_job<'a>(&'a self, id: i64, owner: &'a str, next_run: Option<i64>, error: Option<&'a str>) -> StorageFuture<'a, ()>;
+    /// Every job, soonest first.
+    fn get_jobs(&self) -> StorageFuture<'_, Vec<Job>>;
+    fn cancel_job(&self, id: i64) -> StorageFuture<'_, bool>;
+
+    /// Closes the connections once nothing else will be stored.
+    fn close(&self) -> StorageFuture<'_, ()>;
+}
+
+/// Opens the backend for `url`: `sqlite:` urls when built with the `sqlite` feature, Postgres otherwise.
+pub async fn connect(url: &str) -> Result<Box<dyn Storage>> {
+    #[cfg(feature = "sqlite")]
+    if url.starts_with("sqlite:") {
+        return Ok(Box::new(SqliteStorage::connect(url).await?));
+    }
+
+    Ok(Box::new(PostgresStorage::connect(url).await?))
+}
+
+impl Bot {
+    /// Creates a queue for every stored board so paused boards stay paused across restarts,
+    /// and puts back anyone who was waiting when the bot last shut down.
+    pub async fn load_boards(&self) -> Result<()> {
+        let boards = self.storage.get_boards().await?;
+
+        for board in boards {
+            self.board_or_insert(board.message_id, board.kind, board.paused).await;
+        }
+
+        for saved in self.storage.take_queues().await? {
+            let Some(queue) = self.board(saved.board).await else {
+                continue;
+            };
+
+            let waited = Duration::from_secs(saved.waited.max(0) as u64);
+            let entry = QueueEntry {
+                user: saved.user,
+                joined: Instant::now().checked_sub(waited).unwrap_or_else(Instant::now),
+                world_level: saved.world_level,
+                targets: saved.targets,
+            };
+
+            queue.lock().await.queues.restore(saved.role, entry);
+        }
+
+        Ok(())
+    }
+
+    /// Stores everyone still waiting so `load_boards` can restore them after a restart.
+    pub async fn save_queues(&self) -> Result<()> {
+        let mut entries = Vec::new();
+
+        for (board, queue) in self.boards().await {
+            let queue = queue.lock().await;
+            for role in [Role::Coop, Role::Carry, Role::Assist] {
+                for entry in queue.queues.queue(role) {
+                    entries.push(SavedEntry {
+                        board,
+                        role,
+                        user: entry.user,
+                        world_level: entry.world_level,
+                        targets: entry.targets.clone(),
+                        waited: entry.joined.elapsed().as_secs() as i64,
+                    });
+                }
+            }
+        }
+
+        self.storage.save_queues(&entries).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use twilight_model::id::Id;
+
+    use super::{GatewaySession, MemoryStorage, Profile, SavedEntry, Storage};
+    use crate::interactions::board::BoardKind;
+    use crate::matchmaking::Role;
+
+    /// Runs the same checks against any backend so they stay interchangeable.
+    async fn exercise(storage: &dyn Storage) {
+        storage.setup().await.expect("setup");
+
+        let (alice, bob, carol) = (Id::new(1), Id::new(2), Id::new(3));
+        let (thread, board, channel, guild) = (Id::new(10), Id::new(20), Id::new(30), Id::new(40));
+
+        storage.update_user(alice, 2).await.expect("update_user");
+        storage.update_user(alice, -1).await.expect("update_user");
+        assert_eq!(storage.get_rep(alice).await.expect("get_rep"), Some(1));
+        assert_eq!(storage.get_rep(bob).await.expect("get_rep"), None);
+
+        let change = storage.adjust_rep(alice, bob, "set", Box::new(|_| 7), "test").await.expect("adjust_rep");
+        assert_eq!(change, (1, 7));
+
+        let profile = Profile { uid: "500".to_owned(), name: "Rover".to_owned(), world_level: 6, region: "EU".to_owned() };
+        storage.set_profile(bob, &profile).await.expect("set_profile");
+        assert_eq!(storage.get_profile(bob).await.expect("get_profile").map(|i| i.world_level), Some(6));
+
+        storage.insert_thread(thread, board, &[alice, bob]).await.expect("insert_thread");
+        assert!(storage.is_thread(thread).await.expect("is_thread"));
+        assert!(storage.in_thread(alice).await.expect("in_thread"));
+        assert!(!storage.in_thread(carol).await.expect("in_thread"));
+        assert_eq!(storage.get_session(thread).await.expect("get_session").map(|i| i.users.len()), Some(2));
+
+        assert!(storage.abandon_thread(thread, alice, 2).await.expect("abandon_thread"));
+        assert!(!storage.abandon_thread(thread, alice, 2).await.expect("abandon_thread"));
+        assert_eq!(storage.get_rep(alice).await.expect("get_rep"), Some(5));
+        assert_eq!(storage.get_thread(thread).await.expect("get_thread"), Some(vec![bob]));
+        assert!(storage.fill_thread(thread, 0, carol).await.expect("fill_thread"));
+        assert!(!storage.fill_thread(thread, 0, alice).await.expect("fill_thread"));
+        assert_eq!(storage.get_thread(thread).await.expect("get_thread"), Some(vec![carol, bob]));
+
+        storage.set_end_request(thread, "abc", carol).await.expect("set_end_request");
+        assert_eq!(storage.get_end_request(thread).await.expect("get_end_request"), Some(("abc".to_owned(), carol)));
+
+        assert_eq!(storage.get_threads().await.expect("get_threads"), vec![thread]);
+        assert!(storage.remove_thread(thread).await.expect("remove_thread"));
+        assert!(!storage.remove_thread(thread).await.expect("remove_thread"));
+        assert!(!storage.is_thread(thread).await.expect("is_thread"));
+        assert!(storage.get_threads().await.expect("get_threads").is_empty());
+
+        storage.insert_thread(thread, board, &[alice, carol]).await.expect("insert_thread");
+        assert!(storage.finish_thread(thread, &[(alice, 1), (carol, 2)]).await.expect("finish_thread"));
+        assert!(!storage.finish_thread(thread, &[(alice, 1), (carol, 2)]).await.expect("finish_thread"));
+        assert!(!storage.is_thread(thread).await.expect("is_thread"));
+        assert_eq!(storage.get_rep(alice).await.expect("get_rep"), Some(6));
+        assert_eq!(storage.get_rep(carol).await.expect("get_rep"), Some(2));
+
+        storage.insert_report_draft("token", alice, thread, &[bob, carol]).await.expect("insert_report_draft");
+        storage.set_report_draft_message("token", channel, Id::new(50)).await.expect("set_report_draft_message");
+        storage.update_report_draft("token", &[carol], &[bob]).await.expect("update_report_draft");
+        let draft = storage.get_report_draft("token").await.expect("get_report_draft").expect("draft exists");
+        assert_eq!((draft.remaining, draft.selected, draft.message), (vec![carol], vec![bob], Some((channel, Id::new(50)))));
+        assert!(storage.claim_report_draft("token", &[bob], &[carol], 1).await.expect("claim_report_draft"));
+        assert!(!storage.claim_report_draft("token", &[bob], &[carol], 1).await.expect("claim_report_draft"));
+        assert_eq!(storage.get_rep(bob).await.expect("get_rep"), Some(-1));
+        let draft = storage.get_report_draft("token").await.expect("get_report_draft").expect("draft exists");
+        assert_eq!((draft.remaining, draft.selected), (vec![carol], vec![]));
+        storage.remove_report_draft("token").await.expect("remove_report_draft");
+        assert!(storage.get_report_draft("token").await.expect("get_report_draft").is_none());
+
+        storage.insert_board(board, channel, guild, BoardKind::Weekly).await.expect("insert_board");
+        storage.insert_board(Id::new(21), channel, guild, BoardKind::Elite).await.expect("insert_board");
+        assert_eq!(storage.get_default_board(guild).await.expect("get_default_board"), Some((Id::new(21), channel)));
+        assert_eq!(storage.get_board_kind(board).await.expect("get_board_kind"), BoardKind::Weekly);
+        assert_eq!(storage.get_board_kind(Id::new(99)).await.expect("get_board_kind"), BoardKind::Elite);
+        storage.set_board_paused(board, true).await.expect("set_board_paused");
+        assert!(storage.get_boards().await.expect("get_boards").iter().any(|i| i.message_id == board && i.paused));
+
+        assert!(storage.add_mod_role(guild, Id::new(60)).await.expect("add_mod_role"));
+        assert!(!storage.add_mod_role(guild, Id::new(60)).await.expect("add_mod_role"));
+        assert_eq!(storage.get_mod_roles(guild).await.expect("get_mod_roles"), vec![Id::new(60)]);
+        assert!(storage.remove_mod_role(guild, Id::new(60)).await.expect("remove_mod_role"));
+        assert!(storage.get_mod_roles(guild).await.expect("get_mod_roles").is_empty());
+
+        let saved = [
+            SavedEntry { board, role: Role::Carry, user: bob, world_level: Some(6), targets: vec!["Dreamless".to_owned(), "Jué".to_owned()], waited: 90 },
+            SavedEntry { board, role: Role::Assist, user: alice, world_level: None, targets: Vec::new(), waited: 30 },
+        ];
+        storage.save_queues(&saved[..1]).await.expect("save_queues");
+        storage.save_queues(&saved).await.expect("save_queues");
+        assert_eq!(storage.take_queues().await.expect("take_queues"), saved);
+        assert!(storage.take_queues().await.expect("take_queues").is_empty());
+
+        let sessions = [
+            GatewaySession { shard: 0, total: 2, session_id: "a".to_owned(), sequence: 41 },
+            GatewaySession { shard: 1, total: 2, session_id: "b".to_owned(), sequence: u64::from(u32::MAX) + 1 },
+        ];
+        storage.save_gateway_sessions(&sessions[..1]).await.expect("save_gateway_sessions");
+        storage.save_gateway_sessions(&sessions).await.expect("save_gateway_sessions");
+        assert_eq!(storage.take_gateway_sessions().await.expect("take_gateway_sessions"), sessions);
+        assert!(storage.take_gateway_sessions().await.expect("take_gateway_sessions").is_empty());
+
+        storage.schedule_job("cleanup", "cleanup", "", 100, Some(60)).await.expect("schedule_job");
+        storage.schedule_job("cleanup", "cleanup", "all", 500, Some(30)).await.expect("schedule_job");
+        storage.schedule_job("reminder", "reminder", "10", 200, None).await.expect("schedule_job");
+        let jobs = storage.get_jobs().await.expect("get_jobs");
+        assert_eq!(jobs.iter().map(|i| (i.name.as_str(), i.payload.as_str(), i.run_at, i.interval)).collect::<Vec<_>>(),
+                   vec![("cleanup", "all", 100, Some(30)), ("reminder", "10", 200, None)]);
+
+        // Only one instance gets a due job, until its lease runs out.
+        let claimed = storage.claim_jobs("a", 150, 60).await.expect("claim_jobs");
+        assert_eq!(claimed.iter().map(|i| (i.name.as_str(), i.lease_until)).collect::<Vec<_>>(), vec![("cleanup", Some(210))]);
+        assert!(storage.claim_jobs("b", 160, 60).await.expect("claim_jobs").is_empty());
+        storage.finish_job(claimed[0].id, "b", None, None).await.expect("finish_job");
+        assert_eq!(storage.get_jobs().await.expect("get_jobs").len(), 2);
+        let reclaimed = storage.claim_jobs("b", 211, 60).await.expect("claim_jobs");
+        assert_eq!(reclaimed.len(), 2);
+
+        let cleanup = reclaimed.iter().find(|i| i.name == "cleanup").expect("cleanup was claimed");
+        let reminder = reclaimed.iter().find(|i| i.name == "reminder").expect("reminder was claimed");
+        storage.finish_job(cleanup.id, "b", Some(241), Some("failed")).await.expect("finish_job");
+        storage.finish_job(reminder.id, "b", None, None).await.expect("finish_job");
+        let jobs = storage.get_jobs().await.expect("get_jobs");
+        assert_eq!(jobs.iter().map(|i| (i.name.as_str(), i.run_at, i.lease_until, i.last_error.as_deref())).collect::<Vec<_>>(),
+                   vec![("cleanup", 241, None, Some("failed"))]);
+
+        assert!(storage.cancel_job(jobs[0].id).await.expect("cancel_job"));
+        assert!(!storage.cancel_job(jobs[0].id).await.expect("cancel_job"));
+        assert!(storage.get_jobs().await.expect("get_jobs").is_empty());
+    }
+
+    #[tokio::test]
+    async fn memory() {
+        exercise(&MemoryStorage::new()).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite() {
+        let storage = super::SqliteStorage::connect("sqlite::memory:").await.expect("connect");
+        exercise(&storage).await;
+    }
+}
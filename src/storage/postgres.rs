@@ -0,0 +1,967 @@
+use sqlx::{query, query_as, query_scalar, Postgres, PgPool};
+use twilight_model::id::{
+        Id,
+        marker::{
+            UserMarker,
+            ChannelMarker,
+            GuildMarker,
+            MessageMarker,
+            RoleMarker,
+        }
+    };
+
+use crate::error::Result;
+use crate::interactions::board::BoardKind;
+use crate::matchmaking::Role;
+use super::{Board, GatewaySession, Job, Profile, ReportDraft, SavedEntry, Session, Storage, StorageFuture, REPORT_DRAFT_TTL};
+
+trait Encode<'a, T: sqlx::Encode<'a, Postgres>> {
+    fn encode(&self) -> T;
+}
+
+impl<T> Encode<'_, i64> for Id<T> {
+    #[allow(clippy::cast_possible_wrap)]
+    fn encode(&self) -> i64 {
+        self.get() as i64
+    }
+}
+
+pub struct PostgresStorage {
+    db: PgPool,
+}
+
+impl PostgresStorage {
+    pub async fn connect(url: &str) -> Result<Self> {
+        Ok(Self::new(PgPool::connect(url).await?))
+    }
+
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+impl Storage for PostgresStorage {
+    fn setup(&self) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            query!(
+                "CREATE TABLE IF NOT EXISTS threads (channel_id BIGINT UNIQUE NOT NULL, user1 BIGINT NOT NULL, user2 BIGINT NOT NULL, user3 BIGINT NOT NULL);"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                 "CREATE TABLE IF NOT EXISTS users (user_id BIGINT UNIQUE NOT NULL, rep SERIAL NOT NULL);"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "ALTER TABLE threads ADD COLUMN IF NOT EXISTS end_request TEXT, ADD COLUMN IF NOT EXISTS end_requester BIGINT;"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "ALTER TABLE threads ADD COLUMN IF NOT EXISTS board BIGINT, ALTER COLUMN user1 DROP NOT NULL, ALTER COLUMN user2 DROP NOT NULL, ALTER COLUMN user3 DROP NOT NULL;"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "CREATE TABLE IF NOT EXISTS boards (id SERIAL, message_id BIGINT UNIQUE NOT NULL, channel_id BIGINT NOT NULL, guild_id BIGINT NOT NULL);"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "ALTER TABLE boards ADD COLUMN IF NOT EXISTS paused BOOLEAN NOT NULL DEFAULT FALSE;"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "CREATE TABLE IF NOT EXISTS rep_audit (id SERIAL, user_id BIGINT NOT NULL, moderator BIGINT NOT NULL, action TEXT NOT NULL, old_rep INTEGER NOT NULL, new_rep INTEGER NOT NULL, reason TEXT NOT NULL, created BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM now())::BIGINT);"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "CREATE TABLE IF NOT EXISTS mod_roles (guild_id BIGINT NOT NULL, role_id BIGINT NOT NULL, UNIQUE (guild_id, role_id));"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "CREATE TABLE IF NOT EXISTS profiles (user_id BIGINT UNIQUE NOT NULL, uid TEXT NOT NULL, name TEXT NOT NULL, world_level INTEGER NOT NULL, region TEXT NOT NULL);"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "CREATE TABLE IF NOT EXISTS abandons (channel_id BIGINT NOT NULL, user_id BIGINT NOT NULL, penalty INTEGER NOT NULL);"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "ALTER TABLE boards ADD COLUMN IF NOT EXISTS kind TEXT NOT NULL DEFAULT 'elite';"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "ALTER TABLE threads ADD COLUMN IF NOT EXISTS size INTEGER NOT NULL DEFAULT 3;"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "CREATE TABLE IF NOT EXISTS report_drafts (token TEXT UNIQUE NOT NULL, reporter BIGINT NOT NULL, session BIGINT NOT NULL, channel_id BIGINT, message_id BIGINT, remaining BIGINT[] NOT NULL, selected BIGINT[] NOT NULL DEFAULT '{}', created BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM now())::BIGINT);"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "CREATE TABLE IF NOT EXISTS saved_queues (position INTEGER NOT NULL, board BIGINT NOT NULL, role TEXT NOT NULL, user_id BIGINT NOT NULL, world_level INTEGER, targets TEXT[] NOT NULL, waited BIGINT NOT NULL);"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "CREATE TABLE IF NOT EXISTS gateway_sessions (shard BIGINT NOT NULL, total BIGINT NOT NULL, session_id TEXT NOT NULL, sequence BIGINT NOT NULL);"
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "CREATE TABLE IF NOT EXISTS jobs (id BIGSERIAL PRIMARY KEY, name TEXT UNIQUE NOT NULL, kind TEXT NOT NULL, payload TEXT NOT NULL, run_at BIGINT NOT NULL, interval_secs BIGINT, lease_owner TEXT, lease_until BIGINT, last_error TEXT);"
+            )
+            .execute(&self.db)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn update_user(&self, user_id: Id<UserMarker>, change: i32) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            query!(
+                "INSERT INTO users (user_id, rep) VALUES ($1, 0) ON CONFLICT (user_id) DO NOTHING",
+                user_id.encode(),
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "UPDATE users SET rep = rep + $2 WHERE user_id = $1",
+                user_id.encode(),
+                change,
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn adjust_rep<'a>(&'a self, user_id: Id<UserMarker>, moderator: Id<UserMarker>, action: &'a str, rep: Box<dyn FnOnce(i32) -> i32 + Send + 'a>, reason: &'a str) -> StorageFuture<'a, (i32, i32)> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            query!(
+                "INSERT INTO users (user_id, rep) VALUES ($1, 0) ON CONFLICT (user_id) DO NOTHING",
+                user_id.encode(),
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            let old = query_scalar!(
+                "SELECT rep FROM users WHERE user_id = $1 FOR UPDATE",
+                user_id.encode(),
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            let new = rep(old);
+
+            query!(
+                "UPDATE users SET rep = $2 WHERE user_id = $1",
+                user_id.encode(),
+                new,
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            query!(
+                "INSERT INTO rep_audit (user_id, moderator, action, old_rep, new_rep, reason) VALUES ($1, $2, $3, $4, $5, $6)",
+                user_id.encode(),
+                moderator.encode(),
+                action,
+                old,
+                new,
+                reason,
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+
+            Ok((old, new))
+        })
+    }
+
+    fn get_rep(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, Option<i32>> {
+        Box::pin(async move {
+            Ok(query_scalar!(
+                "SELECT rep FROM users WHERE user_id = $1",
+                user_id.encode()
+            )
+            .fetch_optional(&self.db)
+            .await?)
+        })
+    }
+
+    fn set_profile<'a>(&'a self, user_id: Id<UserMarker>, profile: &'a Profile) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query!(
+                "INSERT INTO profiles (user_id, uid, name, world_level, region) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (user_id) DO UPDATE SET uid = $2, name = $3, world_level = $4, region = $5",
+                user_id.encode(),
+                profile.uid,
+                profile.name,
+                profile.world_level,
+                profile.region,
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_profile(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, Option<Profile>> {
+        Box::pin(async move {
+            Ok(query_as!(
+                Profile,
+                "SELECT uid, name, world_level, region FROM profiles WHERE user_id = $1",
+                user_id.encode(),
+            )
+            .fetch_optional(&self.db)
+            .await?)
+        })
+    }
+
+    fn insert_thread<'a>(&'a self, channel_id: Id<ChannelMarker>, board: Id<MessageMarker>, users: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query!(
+                "INSERT INTO threads (channel_id, board, size, user1, user2, user3) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (channel_id) DO NOTHING",
+                channel_id.encode(),
+                board.encode(),
+                users.len() as i32,
+                users.first().map(|i| i.encode()),
+                users.get(1).map(|i| i.encode()),
+                users.get(2).map(|i| i.encode()),
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn is_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query_scalar!(
+                "SELECT count(1) > 0 FROM threads WHERE channel_id = $1",
+                channel_id.encode()
+            )
+            .fetch_one(&self.db)
+            .await?
+            .unwrap_or(false))
+        })
+    }
+
+    fn in_thread(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query_scalar!(
+                "SELECT count(1) > 0 FROM threads WHERE $1 in (user1, user2, user3)",
+                user_id.encode()
+            )
+            .fetch_one(&self.db)
+            .await?
+            .unwrap_or(false))
+        })
+    }
+
+    fn get_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<Vec<Id<UserMarker>>>> {
+        Box::pin(async move {
+            match query!(
+                "SELECT user1, user2, user3 FROM threads WHERE channel_id = $1;",
+                channel_id.encode(),
+            )
+            .fetch_optional(&self.db)
+            .await? {
+                Some(group) => Ok(Some([group.user1, group.user2, group.user3].into_iter()
+                                            .flatten()
+                                            .map(|i| Id::<UserMarker>::new(i as u64))
+                                            .collect())),
+                _ => Ok(None),
+            }
+        })
+    }
+
+    fn get_session(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<Session>> {
+        Box::pin(async move {
+            match query!(
+                "SELECT board, size, user1, user2, user3 FROM threads WHERE channel_id = $1;",
+                channel_id.encode(),
+            )
+            .fetch_optional(&self.db)
+            .await? {
+                Some(row) => Ok(Some(Session {
+                    board: row.board.map(|i| Id::new(i as u64)),
+                    users: [row.user1, row.user2, row.user3].into_iter()
+                        .take(row.size as usize)
+                        .map(|i| i.map(|i| Id::new(i as u64)))
+                        .collect(),
+                })),
+                _ => Ok(None),
+            }
+        })
+    }
+
+    fn abandon_thread(&self, channel_id: Id<ChannelMarker>, user_id: Id<UserMarker>, penalty: i32) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            let removed = query!(
+                "UPDATE threads SET user1 = NULLIF(user1, $2), user2 = NULLIF(user2, $2), user3 = NULLIF(user3, $2) WHERE channel_id = $1 AND $2 IN (user1, user2, user3)",
+                channel_id.encode(),
+                user_id.encode(),
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() > 0;
+
+            if !removed {
+                return Ok(false);
+            }
+
+            query!(
+                "INSERT INTO abandons (channel_id, user_id, penalty) VALUES ($1, $2, $3)",
+                channel_id.encode(),
+                user_id.encode(),
+                penalty,
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            if penalty != 0 {
+                query!(
+                    "INSERT INTO users (user_id, rep) VALUES ($1, $2) ON CONFLICT (user_id) DO UPDATE SET rep = users.rep + excluded.rep",
+                    user_id.encode(),
+                    -penalty,
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok(true)
+        })
+    }
+
+    fn fill_thread(&self, channel_id: Id<ChannelMarker>, slot: i32, user_id: Id<UserMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query!(
+                "UPDATE threads SET user1 = CASE WHEN $2 = 0 THEN $3 ELSE user1 END, user2 = CASE WHEN $2 = 1 THEN $3 ELSE user2 END, user3 = CASE WHEN $2 = 2 THEN $3 ELSE user3 END WHERE channel_id = $1 AND CASE $2::INT WHEN 0 THEN user1 WHEN 1 THEN user2 WHEN 2 THEN user3 END IS NULL",
+                channel_id.encode(),
+                slot,
+                user_id.encode(),
+            )
+            .execute(&self.db)
+            .await?
+            .rows_affected() > 0)
+        })
+    }
+
+    fn set_end_request<'a>(&'a self, channel_id: Id<ChannelMarker>, request: &'a str, user_id: Id<UserMarker>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query!(
+                "UPDATE threads SET end_request = $2, end_requester = $3 WHERE channel_id = $1;",
+                channel_id.encode(),
+                request,
+                user_id.encode(),
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_end_request(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<(String, Id<UserMarker>)>> {
+        Box::pin(async move {
+            match query!(
+                "SELECT end_request, end_requester FROM threads WHERE channel_id = $1;",
+                channel_id.encode(),
+            )
+            .fetch_optional(&self.db)
+            .await? {
+                Some(row) => match (row.end_request, row.end_requester) {
+                    (Some(request), Some(user)) => Ok(Some((request, Id::new(user as u64)))),
+                    _ => Ok(None),
+                },
+                _ => Ok(None),
+            }
+        })
+    }
+
+    fn remove_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query!(
+                "DELETE FROM threads WHERE channel_id = $1;",
+                channel_id.encode()
+            )
+            .execute(&self.db)
+            .await?
+            .rows_affected() > 0)
+        })
+    }
+
+    fn finish_thread<'a>(&'a self, channel_id: Id<ChannelMarker>, rep: &'a [(Id<UserMarker>, i32)]) -> StorageFuture<'a, bool> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            let removed = query!(
+                "DELETE FROM threads WHERE channel_id = $1",
+                channel_id.encode(),
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() > 0;
+
+            if !removed {
+                return Ok(false);
+            }
+
+            for (user_id, change) in rep {
+                query!(
+                    "INSERT INTO users (user_id, rep) VALUES ($1, $2) ON CONFLICT (user_id) DO UPDATE SET rep = users.rep + excluded.rep",
+                    user_id.encode(),
+                    change,
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok(true)
+        })
+    }
+
+    fn get_threads(&self) -> StorageFuture<'_, Vec<Id<ChannelMarker>>> {
+        Box::pin(async move {
+            Ok(query_scalar!(
+                "SELECT channel_id FROM threads"
+            )
+            .fetch_all(&self.db)
+            .await?
+            .into_iter()
+            .map(|i| Id::new(i as u64))
+            .collect())
+        })
+    }
+
+    fn insert_report_draft<'a>(&'a self, token: &'a str, reporter: Id<UserMarker>, session: Id<ChannelMarker>, remaining: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query!(
+                "DELETE FROM report_drafts WHERE created < EXTRACT(EPOCH FROM now())::BIGINT - $1",
+                REPORT_DRAFT_TTL,
+            )
+            .execute(&self.db)
+            .await?;
+
+            query!(
+                "INSERT INTO report_drafts (token, reporter, session, remaining) VALUES ($1, $2, $3, $4)",
+                token,
+                reporter.encode(),
+                session.encode(),
+                &remaining.iter().map(|i| i.encode()).collect::<Vec<i64>>(),
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn set_report_draft_message<'a>(&'a self, token: &'a str, channel_id: Id<ChannelMarker>, message_id: Id<MessageMarker>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query!(
+                "UPDATE report_drafts SET channel_id = $2, message_id = $3 WHERE token = $1",
+                token,
+                channel_id.encode(),
+                message_id.encode(),
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_report_draft<'a>(&'a self, token: &'a str) -> StorageFuture<'a, Option<ReportDraft>> {
+        Box::pin(async move {
+            Ok(query!(
+                "SELECT reporter, session, channel_id, message_id, remaining, selected FROM report_drafts WHERE token = $1",
+                token,
+            )
+            .fetch_optional(&self.db)
+            .await?
+            .map(|row| ReportDraft {
+                reporter: Id::new(row.reporter as u64),
+                session: Id::new(row.session as u64),
+                message: row.channel_id.zip(row.message_id).map(|(channel, message)| (Id::new(channel as u64), Id::new(message as u64))),
+                remaining: row.remaining.into_iter().map(|i| Id::new(i as u64)).collect(),
+                selected: row.selected.into_iter().map(|i| Id::new(i as u64)).collect(),
+            }))
+        })
+    }
+
+    fn update_report_draft<'a>(&'a self, token: &'a str, remaining: &'a [Id<UserMarker>], selected: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query!(
+                "UPDATE report_drafts SET remaining = $2, selected = $3 WHERE token = $1",
+                token,
+                &remaining.iter().map(|i| i.encode()).collect::<Vec<i64>>(),
+                &selected.iter().map(|i| i.encode()).collect::<Vec<i64>>(),
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn claim_report_draft<'a>(&'a self, token: &'a str, selected: &'a [Id<UserMarker>], remaining: &'a [Id<UserMarker>], penalty: i32) -> StorageFuture<'a, bool> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            let claimed = query!(
+                "UPDATE report_drafts SET remaining = $3, selected = '{}' WHERE token = $1 AND selected = $2",
+                token,
+                &selected.iter().map(|i| i.encode()).collect::<Vec<i64>>(),
+                &remaining.iter().map(|i| i.encode()).collect::<Vec<i64>>(),
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() > 0;
+
+            if !claimed {
+                return Ok(false);
+            }
+
+            if penalty != 0 {
+                for user in selected {
+                    query!(
+                        "INSERT INTO users (user_id, rep) VALUES ($1, $2) ON CONFLICT (user_id) DO UPDATE SET rep = users.rep + excluded.rep",
+                        user.encode(),
+                        -penalty,
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                }
+            }
+
+            tx.commit().await?;
+
+            Ok(true)
+        })
+    }
+
+    fn remove_report_draft<'a>(&'a self, token: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query!(
+                "DELETE FROM report_drafts WHERE token = $1",
+                token,
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn insert_board(&self, message_id: Id<MessageMarker>, channel_id: Id<ChannelMarker>, guild_id: Id<GuildMarker>, kind: BoardKind) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            query!(
+                "INSERT INTO boards (message_id, channel_id, guild_id, kind) VALUES ($1, $2, $3, $4) ON CONFLICT (message_id) DO NOTHING",
+                message_id.encode(),
+                channel_id.encode(),
+                guild_id.encode(),
+                kind.value(),
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_default_board(&self, guild_id: Id<GuildMarker>) -> StorageFuture<'_, Option<(Id<MessageMarker>, Id<ChannelMarker>)>> {
+        Box::pin(async move {
+            Ok(query!(
+                "SELECT message_id, channel_id FROM boards WHERE guild_id = $1 ORDER BY id DESC LIMIT 1",
+                guild_id.encode(),
+            )
+            .fetch_optional(&self.db)
+            .await?
+            .map(|row| (Id::new(row.message_id as u64), Id::new(row.channel_id as u64))))
+        })
+    }
+
+    fn get_channel_board(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<(Id<MessageMarker>, Id<ChannelMarker>)>> {
+        Box::pin(async move {
+            Ok(query!(
+                "SELECT message_id, channel_id FROM boards WHERE channel_id = $1 ORDER BY id DESC LIMIT 1",
+                channel_id.encode(),
+            )
+            .fetch_optional(&self.db)
+            .await?
+            .map(|row| (Id::new(row.message_id as u64), Id::new(row.channel_id as u64))))
+        })
+    }
+
+    fn get_board_channel(&self, message_id: Id<MessageMarker>) -> StorageFuture<'_, Option<Id<ChannelMarker>>> {
+        Box::pin(async move {
+            Ok(query_scalar!(
+                "SELECT channel_id FROM boards WHERE message_id = $1",
+                message_id.encode(),
+            )
+            .fetch_optional(&self.db)
+            .await?
+            .map(|i| Id::new(i as u64)))
+        })
+    }
+
+    fn get_board_kind(&self, message_id: Id<MessageMarker>) -> StorageFuture<'_, BoardKind> {
+        Box::pin(async move {
+            Ok(query_scalar!(
+                "SELECT kind FROM boards WHERE message_id = $1",
+                message_id.encode(),
+            )
+            .fetch_optional(&self.db)
+            .await?
+            .map_or(BoardKind::Elite, |i| BoardKind::from_value(&i)))
+        })
+    }
+
+    fn set_board_paused(&self, message_id: Id<MessageMarker>, paused: bool) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            query!(
+                "UPDATE boards SET paused = $2 WHERE message_id = $1",
+                message_id.encode(),
+                paused,
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_boards(&self) -> StorageFuture<'_, Vec<Board>> {
+        Box::pin(async move {
+            Ok(query!(
+                "SELECT message_id, kind, paused FROM boards"
+            )
+            .fetch_all(&self.db)
+            .await?
+            .into_iter()
+            .map(|row| Board {
+                message_id: Id::new(row.message_id as u64),
+                kind: BoardKind::from_value(&row.kind),
+                paused: row.paused,
+            })
+            .collect())
+        })
+    }
+
+    fn add_mod_role(&self, guild_id: Id<GuildMarker>, role_id: Id<RoleMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query!(
+                "INSERT INTO mod_roles (guild_id, role_id) VALUES ($1, $2) ON CONFLICT (guild_id, role_id) DO NOTHING",
+                guild_id.encode(),
+                role_id.encode(),
+            )
+            .execute(&self.db)
+            .await?
+            .rows_affected() > 0)
+        })
+    }
+
+    fn remove_mod_role(&self, guild_id: Id<GuildMarker>, role_id: Id<RoleMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query!(
+                "DELETE FROM mod_roles WHERE guild_id = $1 AND role_id = $2",
+                guild_id.encode(),
+                role_id.encode(),
+            )
+            .execute(&self.db)
+            .await?
+            .rows_affected() > 0)
+        })
+    }
+
+    fn get_mod_roles(&self, guild_id: Id<GuildMarker>) -> StorageFuture<'_, Vec<Id<RoleMarker>>> {
+        Box::pin(async move {
+            Ok(query_scalar!(
+                "SELECT role_id FROM mod_roles WHERE guild_id = $1",
+                guild_id.encode(),
+            )
+            .fetch_all(&self.db)
+            .await?
+            .into_iter()
+            .map(|i| Id::new(i as u64))
+            .collect())
+        })
+    }
+
+    fn save_queues<'a>(&'a self, entries: &'a [SavedEntry]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            query!(
+                "DELETE FROM saved_queues"
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            for (position, entry) in entries.iter().enumerate() {
+                query!(
+                    "INSERT INTO saved_queues (position, board, role, user_id, world_level, targets, waited) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                    position as i32,
+                    entry.board.encode(),
+                    entry.role.value(),
+                    entry.user.encode(),
+                    entry.world_level,
+                    &entry.targets,
+                    entry.waited,
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok(())
+        })
+    }
+
+    fn take_queues(&self) -> StorageFuture<'_, Vec<SavedEntry>> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            let rows = query!(
+                "SELECT board, role, user_id, world_level, targets, waited FROM saved_queues ORDER BY position"
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+
+            query!(
+                "DELETE FROM saved_queues"
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+
+            Ok(rows.into_iter()
+                .filter_map(|row| Some(SavedEntry {
+                    board: Id::new(row.board as u64),
+                    role: Role::from_value(&row.role)?,
+                    user: Id::new(row.user_id as u64),
+                    world_level: row.world_level,
+                    targets: row.targets,
+                    waited: row.waited,
+                }))
+                .collect())
+        })
+    }
+
+    fn save_gateway_sessions<'a>(&'a self, sessions: &'a [GatewaySession]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            query!(
+                "DELETE FROM gateway_sessions"
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            for session in sessions {
+                query!(
+                    "INSERT INTO gateway_sessions (shard, total, session_id, sequence) VALUES ($1, $2, $3, $4)",
+                    session.shard as i64,
+                    session.total as i64,
+                    session.session_id,
+                    session.sequence as i64,
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok(())
+        })
+    }
+
+    fn take_gateway_sessions(&self) -> StorageFuture<'_, Vec<GatewaySession>> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            let rows = query!(
+                "SELECT shard, total, session_id, sequence FROM gateway_sessions ORDER BY shard"
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+
+            query!(
+                "DELETE FROM gateway_sessions"
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+
+            Ok(rows.into_iter()
+                .map(|row| GatewaySession {
+                    shard: row.shard as u64,
+                    total: row.total as u64,
+                    session_id: row.session_id,
+                    sequence: row.sequence as u64,
+                })
+                .collect())
+        })
+    }
+
+    fn schedule_job<'a>(&'a self, name: &'a str, kind: &'a str, payload: &'a str, run_at: i64, interval: Option<i64>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query!(
+                "INSERT INTO jobs (name, kind, payload, run_at, interval_secs) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (name) DO UPDATE SET kind = $2, payload = $3, run_at = CASE WHEN $5::BIGINT IS NULL THEN $4 ELSE jobs.run_at END, interval_secs = $5",
+                name,
+                kind,
+                payload,
+                run_at,
+                interval,
+            )
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn claim_jobs<'a>(&'a self, owner: &'a str, now: i64, lease: i64) -> StorageFuture<'a, Vec<Job>> {
+        Box::pin(async move {
+            // Rows are locked as they are updated, so a second instance re-checks the lease and skips them.
+            let mut jobs = query!(
+                "UPDATE jobs SET lease_owner = $1, lease_until = $2 + $3 WHERE run_at <= $2 AND (lease_until IS NULL OR lease_until <= $2) RETURNING id, name, kind, payload, run_at, interval_secs, lease_until, last_error",
+                owner,
+                now,
+                lease,
+            )
+            .fetch_all(&self.db)
+            .await?
+            .into_iter()
+            .map(|row| Job {
+                id: row.id,
+                name: row.name,
+                kind: row.kind,
+                payload: row.payload,
+                run_at: row.run_at,
+                interval: row.interval_secs,
+                lease_until: row.lease_until,
+                last_error: row.last_error,
+            })
+            .collect::<Vec<Job>>();
+
+            jobs.sort_by_key(|i| i.run_at);
+
+            Ok(jobs)
+        })
+    }
+
+    fn finish_job<'a>(&'a self, id: i64, owner: &'a str, next_run: Option<i64>, error: Option<&'a str>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            match next_run {
+                Some(next_run) => {
+                    query!(
+                        "UPDATE jobs SET run_at = $3, lease_owner = NULL, lease_until = NULL, last_error = $4 WHERE id = $1 AND lease_owner = $2",
+                        id,
+                        owner,
+                        next_run,
+                        error,
+                    )
+                    .execute(&self.db)
+                    .await?;
+                },
+                None => {
+                    query!(
+                        "DELETE FROM jobs WHERE id = $1 AND lease_owner = $2",
+                        id,
+                        owner,
+                    )
+                    .execute(&self.db)
+                    .await?;
+                },
+            }
+
+            Ok(())
+        })
+    }
+
+    fn get_jobs(&self) -> StorageFuture<'_, Vec<Job>> {
+        Box::pin(async move {
+            Ok(query!(
+                "SELECT id, name, kind, payload, run_at, interval_secs, lease_until, last_error FROM jobs ORDER BY run_at"
+            )
+            .fetch_all(&self.db)
+            .await?
+            .into_iter()
+            .map(|row| Job {
+                id: row.id,
+                name: row.name,
+                kind: row.kind,
+                payload: row.payload,
+                run_at: row.run_at,
+                interval: row.interval_secs,
+                lease_until: row.lease_until,
+                last_error: row.last_error,
+            })
+            .collect())
+        })
+    }
+
+    fn cancel_job(&self, id: i64) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query!(
+                "DELETE FROM jobs WHERE id = $1",
+                id,
+            )
+            .execute(&self.db)
+            .await?
+            .rows_affected() > 0)
+        })
+    }
+
+    fn close(&self) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            self.db.close().await;
+
+            Ok(())
+        })
+    }
+}
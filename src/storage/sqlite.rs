@@ -0,0 +1,767 @@
+use std::str::FromStr;
+
+use sqlx::{query, query_as, query_scalar, SqlitePool};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use twilight_model::id::{
+        Id,
+        marker::{
+            UserMarker,
+            ChannelMarker,
+            GuildMarker,
+            MessageMarker,
+            RoleMarker,
+        }
+    };
+
+use crate::error::Result;
+use crate::interactions::board::BoardKind;
+use crate::matchmaking::Role;
+use super::{Board, GatewaySession, Job, Profile, ReportDraft, SavedEntry, Session, Storage, StorageFuture, REPORT_DRAFT_TTL};
+
+/// SQLite has no arrays, so report draft members and queue targets are stored comma separated.
+const SCHEMA: [&str; 11] = [
+    "CREATE TABLE IF NOT EXISTS threads (channel_id INTEGER UNIQUE NOT NULL, board INTEGER, size INTEGER NOT NULL DEFAULT 3, user1 INTEGER, user2 INTEGER, user3 INTEGER, end_request TEXT, end_requester INTEGER)",
+    "CREATE TABLE IF NOT EXISTS users (user_id INTEGER UNIQUE NOT NULL, rep INTEGER NOT NULL DEFAULT 0)",
+    "CREATE TABLE IF NOT EXISTS boards (id INTEGER PRIMARY KEY AUTOINCREMENT, message_id INTEGER UNIQUE NOT NULL, channel_id INTEGER NOT NULL, guild_id INTEGER NOT NULL, paused BOOLEAN NOT NULL DEFAULT FALSE, kind TEXT NOT NULL DEFAULT 'elite')",
+    "CREATE TABLE IF NOT EXISTS rep_audit (id INTEGER PRIMARY KEY AUTOINCREMENT, user_id INTEGER NOT NULL, moderator INTEGER NOT NULL, action TEXT NOT NULL, old_rep INTEGER NOT NULL, new_rep INTEGER NOT NULL, reason TEXT NOT NULL, created INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER)))",
+    "CREATE TABLE IF NOT EXISTS mod_roles (guild_id INTEGER NOT NULL, role_id INTEGER NOT NULL, UNIQUE (guild_id, role_id))",
+    "CREATE TABLE IF NOT EXISTS profiles (user_id INTEGER UNIQUE NOT NULL, uid TEXT NOT NULL, name TEXT NOT NULL, world_level INTEGER NOT NULL, region TEXT NOT NULL)",
+    "CREATE TABLE IF NOT EXISTS abandons (channel_id INTEGER NOT NULL, user_id INTEGER NOT NULL, penalty INTEGER NOT NULL)",
+    "CREATE TABLE IF NOT EXISTS report_drafts (token TEXT UNIQUE NOT NULL, reporter INTEGER NOT NULL, session INTEGER NOT NULL, channel_id INTEGER, message_id INTEGER, remaining TEXT NOT NULL, selected TEXT NOT NULL DEFAULT '', created INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER)))",
+    "CREATE TABLE IF NOT EXISTS saved_queues (position INTEGER NOT NULL, board INTEGER NOT NULL, role TEXT NOT NULL, user_id INTEGER NOT NULL, world_level INTEGER, targets TEXT NOT NULL, waited INTEGER NOT NULL)",
+    "CREATE TABLE IF NOT EXISTS gateway_sessions (shard INTEGER NOT NULL, total INTEGER NOT NULL, session_id TEXT NOT NULL, sequence INTEGER NOT NULL)",
+    "CREATE TABLE IF NOT EXISTS jobs (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT NULL, kind TEXT NOT NULL, payload TEXT NOT NULL, run_at INTEGER NOT NULL, interval_secs INTEGER, lease_owner TEXT, lease_until INTEGER, last_error TEXT)",
+];
+
+type JobRow = (i64, String, String, String, i64, Option<i64>, Option<i64>, Option<String>);
+
+fn job((id, name, kind, payload, run_at, interval, lease_until, last_error): JobRow) -> Job {
+    Job { id, name, kind, payload, run_at, interval, lease_until, last_error }
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn encode<T>(id: Id<T>) -> i64 {
+    id.get() as i64
+}
+
+fn decode<T>(value: i64) -> Id<T> {
+    Id::new(value as u64)
+}
+
+fn encode_list<T>(ids: &[Id<T>]) -> String {
+    ids.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(",")
+}
+
+fn decode_list<T>(value: &str) -> Vec<Id<T>> {
+    value.split(',')
+        .filter_map(|i| i.parse::<u64>().ok())
+        .filter_map(Id::new_checked)
+        .collect()
+}
+
+/// Storage for small single-server deployments, enabled with the `sqlite` feature.
+pub struct SqliteStorage {
+    db: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub async fn connect(url: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+
+        // A single connection serialises writes and keeps `sqlite::memory:` databases shared.
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+
+        Ok(Self { db })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn setup(&self) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            for statement in SCHEMA {
+                query(statement).execute(&self.db).await?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn update_user(&self, user_id: Id<UserMarker>, change: i32) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            query("INSERT INTO users (user_id, rep) VALUES (?1, 0) ON CONFLICT (user_id) DO NOTHING")
+                .bind(encode(user_id))
+                .execute(&self.db)
+                .await?;
+
+            query("UPDATE users SET rep = rep + ?2 WHERE user_id = ?1")
+                .bind(encode(user_id))
+                .bind(change)
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn adjust_rep<'a>(&'a self, user_id: Id<UserMarker>, moderator: Id<UserMarker>, action: &'a str, rep: Box<dyn FnOnce(i32) -> i32 + Send + 'a>, reason: &'a str) -> StorageFuture<'a, (i32, i32)> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            query("INSERT INTO users (user_id, rep) VALUES (?1, 0) ON CONFLICT (user_id) DO NOTHING")
+                .bind(encode(user_id))
+                .execute(&mut *tx)
+                .await?;
+
+            let old: i32 = query_scalar("SELECT rep FROM users WHERE user_id = ?1")
+                .bind(encode(user_id))
+                .fetch_one(&mut *tx)
+                .await?;
+
+            let new = rep(old);
+
+            query("UPDATE users SET rep = ?2 WHERE user_id = ?1")
+                .bind(encode(user_id))
+                .bind(new)
+                .execute(&mut *tx)
+                .await?;
+
+            query("INSERT INTO rep_audit (user_id, moderator, action, old_rep, new_rep, reason) VALUES (?1, ?2, ?3, ?4, ?5, ?6)")
+                .bind(encode(user_id))
+                .bind(encode(moderator))
+                .bind(action)
+                .bind(old)
+                .bind(new)
+                .bind(reason)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+
+            Ok((old, new))
+        })
+    }
+
+    fn get_rep(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, Option<i32>> {
+        Box::pin(async move {
+            Ok(query_scalar("SELECT rep FROM users WHERE user_id = ?1")
+                .bind(encode(user_id))
+                .fetch_optional(&self.db)
+                .await?)
+        })
+    }
+
+    fn set_profile<'a>(&'a self, user_id: Id<UserMarker>, profile: &'a Profile) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query("INSERT INTO profiles (user_id, uid, name, world_level, region) VALUES (?1, ?2, ?3, ?4, ?5) ON CONFLICT (user_id) DO UPDATE SET uid = ?2, name = ?3, world_level = ?4, region = ?5")
+                .bind(encode(user_id))
+                .bind(&profile.uid)
+                .bind(&profile.name)
+                .bind(profile.world_level)
+                .bind(&profile.region)
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_profile(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, Option<Profile>> {
+        Box::pin(async move {
+            Ok(query_as::<_, (String, String, i32, String)>("SELECT uid, name, world_level, region FROM profiles WHERE user_id = ?1")
+                .bind(encode(user_id))
+                .fetch_optional(&self.db)
+                .await?
+                .map(|(uid, name, world_level, region)| Profile { uid, name, world_level, region }))
+        })
+    }
+
+    fn insert_thread<'a>(&'a self, channel_id: Id<ChannelMarker>, board: Id<MessageMarker>, users: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query("INSERT INTO threads (channel_id, board, size, user1, user2, user3) VALUES (?1, ?2, ?3, ?4, ?5, ?6) ON CONFLICT (channel_id) DO NOTHING")
+                .bind(encode(channel_id))
+                .bind(encode(board))
+                .bind(users.len() as i32)
+                .bind(users.first().map(|i| encode(*i)))
+                .bind(users.get(1).map(|i| encode(*i)))
+                .bind(users.get(2).map(|i| encode(*i)))
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn is_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query_scalar("SELECT count(1) > 0 FROM threads WHERE channel_id = ?1")
+                .bind(encode(channel_id))
+                .fetch_one(&self.db)
+                .await?)
+        })
+    }
+
+    fn in_thread(&self, user_id: Id<UserMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query_scalar("SELECT count(1) > 0 FROM threads WHERE ?1 IN (user1, user2, user3)")
+                .bind(encode(user_id))
+                .fetch_one(&self.db)
+                .await?)
+        })
+    }
+
+    fn get_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<Vec<Id<UserMarker>>>> {
+        Box::pin(async move {
+            Ok(query_as::<_, (Option<i64>, Option<i64>, Option<i64>)>("SELECT user1, user2, user3 FROM threads WHERE channel_id = ?1")
+                .bind(encode(channel_id))
+                .fetch_optional(&self.db)
+                .await?
+                .map(|(user1, user2, user3)| [user1, user2, user3].into_iter()
+                     .flatten()
+                     .map(decode)
+                     .collect()))
+        })
+    }
+
+    fn get_session(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<Session>> {
+        Box::pin(async move {
+            Ok(query_as::<_, (Option<i64>, i32, Option<i64>, Option<i64>, Option<i64>)>("SELECT board, size, user1, user2, user3 FROM threads WHERE channel_id = ?1")
+                .bind(encode(channel_id))
+                .fetch_optional(&self.db)
+                .await?
+                .map(|(board, size, user1, user2, user3)| Session {
+                    board: board.map(decode),
+                    users: [user1, user2, user3].into_iter()
+                        .take(size as usize)
+                        .map(|i| i.map(decode))
+                        .collect(),
+                }))
+        })
+    }
+
+    fn abandon_thread(&self, channel_id: Id<ChannelMarker>, user_id: Id<UserMarker>, penalty: i32) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            let removed = query("UPDATE threads SET user1 = NULLIF(user1, ?2), user2 = NULLIF(user2, ?2), user3 = NULLIF(user3, ?2) WHERE channel_id = ?1 AND ?2 IN (user1, user2, user3)")
+                .bind(encode(channel_id))
+                .bind(encode(user_id))
+                .execute(&mut *tx)
+                .await?
+                .rows_affected() > 0;
+
+            if !removed {
+                return Ok(false);
+            }
+
+            query("INSERT INTO abandons (channel_id, user_id, penalty) VALUES (?1, ?2, ?3)")
+                .bind(encode(channel_id))
+                .bind(encode(user_id))
+                .bind(penalty)
+                .execute(&mut *tx)
+                .await?;
+
+            if penalty != 0 {
+                query("INSERT INTO users (user_id, rep) VALUES (?1, ?2) ON CONFLICT (user_id) DO UPDATE SET rep = users.rep + excluded.rep")
+                    .bind(encode(user_id))
+                    .bind(-penalty)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok(true)
+        })
+    }
+
+    fn fill_thread(&self, channel_id: Id<ChannelMarker>, slot: i32, user_id: Id<UserMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query("UPDATE threads SET user1 = CASE WHEN ?2 = 0 THEN ?3 ELSE user1 END, user2 = CASE WHEN ?2 = 1 THEN ?3 ELSE user2 END, user3 = CASE WHEN ?2 = 2 THEN ?3 ELSE user3 END WHERE channel_id = ?1 AND CASE ?2 WHEN 0 THEN user1 WHEN 1 THEN user2 WHEN 2 THEN user3 END IS NULL")
+                .bind(encode(channel_id))
+                .bind(slot)
+                .bind(encode(user_id))
+                .execute(&self.db)
+                .await?
+                .rows_affected() > 0)
+        })
+    }
+
+    fn set_end_request<'a>(&'a self, channel_id: Id<ChannelMarker>, request: &'a str, user_id: Id<UserMarker>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query("UPDATE threads SET end_request = ?2, end_requester = ?3 WHERE channel_id = ?1")
+                .bind(encode(channel_id))
+                .bind(request)
+                .bind(encode(user_id))
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_end_request(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<(String, Id<UserMarker>)>> {
+        Box::pin(async move {
+            Ok(query_as::<_, (Option<String>, Option<i64>)>("SELECT end_request, end_requester FROM threads WHERE channel_id = ?1")
+                .bind(encode(channel_id))
+                .fetch_optional(&self.db)
+                .await?
+                .and_then(|(request, user)| request.zip(user))
+                .map(|(request, user)| (request, decode(user))))
+        })
+    }
+
+    fn remove_thread(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query("DELETE FROM threads WHERE channel_id = ?1")
+                .bind(encode(channel_id))
+                .execute(&self.db)
+                .await?
+                .rows_affected() > 0)
+        })
+    }
+
+    fn finish_thread<'a>(&'a self, channel_id: Id<ChannelMarker>, rep: &'a [(Id<UserMarker>, i32)]) -> StorageFuture<'a, bool> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            let removed = query("DELETE FROM threads WHERE channel_id = ?1")
+                .bind(encode(channel_id))
+                .execute(&mut *tx)
+                .await?
+                .rows_affected() > 0;
+
+            if !removed {
+                return Ok(false);
+            }
+
+            for (user_id, change) in rep {
+                query("INSERT INTO users (user_id, rep) VALUES (?1, ?2) ON CONFLICT (user_id) DO UPDATE SET rep = users.rep + excluded.rep")
+                    .bind(encode(*user_id))
+                    .bind(change)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok(true)
+        })
+    }
+
+    fn get_threads(&self) -> StorageFuture<'_, Vec<Id<ChannelMarker>>> {
+        Box::pin(async move {
+            Ok(query_scalar::<_, i64>("SELECT channel_id FROM threads")
+                .fetch_all(&self.db)
+                .await?
+                .into_iter()
+                .map(decode)
+                .collect())
+        })
+    }
+
+    fn insert_report_draft<'a>(&'a self, token: &'a str, reporter: Id<UserMarker>, session: Id<ChannelMarker>, remaining: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query("DELETE FROM report_drafts WHERE created < CAST(strftime('%s', 'now') AS INTEGER) - ?1")
+                .bind(REPORT_DRAFT_TTL)
+                .execute(&self.db)
+                .await?;
+
+            query("INSERT INTO report_drafts (token, reporter, session, remaining) VALUES (?1, ?2, ?3, ?4)")
+                .bind(token)
+                .bind(encode(reporter))
+                .bind(encode(session))
+                .bind(encode_list(remaining))
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn set_report_draft_message<'a>(&'a self, token: &'a str, channel_id: Id<ChannelMarker>, message_id: Id<MessageMarker>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query("UPDATE report_drafts SET channel_id = ?2, message_id = ?3 WHERE token = ?1")
+                .bind(token)
+                .bind(encode(channel_id))
+                .bind(encode(message_id))
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_report_draft<'a>(&'a self, token: &'a str) -> StorageFuture<'a, Option<ReportDraft>> {
+        Box::pin(async move {
+            Ok(query_as::<_, (i64, i64, Option<i64>, Option<i64>, String, String)>("SELECT reporter, session, channel_id, message_id, remaining, selected FROM report_drafts WHERE token = ?1")
+                .bind(token)
+                .fetch_optional(&self.db)
+                .await?
+                .map(|(reporter, session, channel_id, message_id, remaining, selected)| ReportDraft {
+                    reporter: decode(reporter),
+                    session: decode(session),
+                    message: channel_id.zip(message_id).map(|(channel, message)| (decode(channel), decode(message))),
+                    remaining: decode_list(&remaining),
+                    selected: decode_list(&selected),
+                }))
+        })
+    }
+
+    fn update_report_draft<'a>(&'a self, token: &'a str, remaining: &'a [Id<UserMarker>], selected: &'a [Id<UserMarker>]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query("UPDATE report_drafts SET remaining = ?2, selected = ?3 WHERE token = ?1")
+                .bind(token)
+                .bind(encode_list(remaining))
+                .bind(encode_list(selected))
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn claim_report_draft<'a>(&'a self, token: &'a str, selected: &'a [Id<UserMarker>], remaining: &'a [Id<UserMarker>], penalty: i32) -> StorageFuture<'a, bool> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            let claimed = query("UPDATE report_drafts SET remaining = ?3, selected = '' WHERE token = ?1 AND selected = ?2")
+                .bind(token)
+                .bind(encode_list(selected))
+                .bind(encode_list(remaining))
+                .execute(&mut *tx)
+                .await?
+                .rows_affected() > 0;
+
+            if !claimed {
+                return Ok(false);
+            }
+
+            if penalty != 0 {
+                for user in selected {
+                    query("INSERT INTO users (user_id, rep) VALUES (?1, ?2) ON CONFLICT (user_id) DO UPDATE SET rep = users.rep + excluded.rep")
+                        .bind(encode(*user))
+                        .bind(-penalty)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+
+            tx.commit().await?;
+
+            Ok(true)
+        })
+    }
+
+    fn remove_report_draft<'a>(&'a self, token: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query("DELETE FROM report_drafts WHERE token = ?1")
+                .bind(token)
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn insert_board(&self, message_id: Id<MessageMarker>, channel_id: Id<ChannelMarker>, guild_id: Id<GuildMarker>, kind: BoardKind) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            query("INSERT INTO boards (message_id, channel_id, guild_id, kind) VALUES (?1, ?2, ?3, ?4) ON CONFLICT (message_id) DO NOTHING")
+                .bind(encode(message_id))
+                .bind(encode(channel_id))
+                .bind(encode(guild_id))
+                .bind(kind.value())
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_default_board(&self, guild_id: Id<GuildMarker>) -> StorageFuture<'_, Option<(Id<MessageMarker>, Id<ChannelMarker>)>> {
+        Box::pin(async move {
+            Ok(query_as::<_, (i64, i64)>("SELECT message_id, channel_id FROM boards WHERE guild_id = ?1 ORDER BY id DESC LIMIT 1")
+                .bind(encode(guild_id))
+                .fetch_optional(&self.db)
+                .await?
+                .map(|(message, channel)| (decode(message), decode(channel))))
+        })
+    }
+
+    fn get_channel_board(&self, channel_id: Id<ChannelMarker>) -> StorageFuture<'_, Option<(Id<MessageMarker>, Id<ChannelMarker>)>> {
+        Box::pin(async move {
+            Ok(query_as::<_, (i64, i64)>("SELECT message_id, channel_id FROM boards WHERE channel_id = ?1 ORDER BY id DESC LIMIT 1")
+                .bind(encode(channel_id))
+                .fetch_optional(&self.db)
+                .await?
+                .map(|(message, channel)| (decode(message), decode(channel))))
+        })
+    }
+
+    fn get_board_channel(&self, message_id: Id<MessageMarker>) -> StorageFuture<'_, Option<Id<ChannelMarker>>> {
+        Box::pin(async move {
+            Ok(query_scalar::<_, i64>("SELECT channel_id FROM boards WHERE message_id = ?1")
+                .bind(encode(message_id))
+                .fetch_optional(&self.db)
+                .await?
+                .map(decode))
+        })
+    }
+
+    fn get_board_kind(&self, message_id: Id<MessageMarker>) -> StorageFuture<'_, BoardKind> {
+        Box::pin(async move {
+            Ok(query_scalar::<_, String>("SELECT kind FROM boards WHERE message_id = ?1")
+                .bind(encode(message_id))
+                .fetch_optional(&self.db)
+                .await?
+                .map_or(BoardKind::Elite, |i| BoardKind::from_value(&i)))
+        })
+    }
+
+    fn set_board_paused(&self, message_id: Id<MessageMarker>, paused: bool) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            query("UPDATE boards SET paused = ?2 WHERE message_id = ?1")
+                .bind(encode(message_id))
+                .bind(paused)
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_boards(&self) -> StorageFuture<'_, Vec<Board>> {
+        Box::pin(async move {
+            Ok(query_as::<_, (i64, String, bool)>("SELECT message_id, kind, paused FROM boards")
+                .fetch_all(&self.db)
+                .await?
+                .into_iter()
+                .map(|(message_id, kind, paused)| Board {
+                    message_id: decode(message_id),
+                    kind: BoardKind::from_value(&kind),
+                    paused,
+                })
+                .collect())
+        })
+    }
+
+    fn add_mod_role(&self, guild_id: Id<GuildMarker>, role_id: Id<RoleMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query("INSERT INTO mod_roles (guild_id, role_id) VALUES (?1, ?2) ON CONFLICT (guild_id, role_id) DO NOTHING")
+                .bind(encode(guild_id))
+                .bind(encode(role_id))
+                .execute(&self.db)
+                .await?
+                .rows_affected() > 0)
+        })
+    }
+
+    fn remove_mod_role(&self, guild_id: Id<GuildMarker>, role_id: Id<RoleMarker>) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query("DELETE FROM mod_roles WHERE guild_id = ?1 AND role_id = ?2")
+                .bind(encode(guild_id))
+                .bind(encode(role_id))
+                .execute(&self.db)
+                .await?
+                .rows_affected() > 0)
+        })
+    }
+
+    fn get_mod_roles(&self, guild_id: Id<GuildMarker>) -> StorageFuture<'_, Vec<Id<RoleMarker>>> {
+        Box::pin(async move {
+            Ok(query_scalar::<_, i64>("SELECT role_id FROM mod_roles WHERE guild_id = ?1")
+                .bind(encode(guild_id))
+                .fetch_all(&self.db)
+                .await?
+                .into_iter()
+                .map(decode)
+                .collect())
+        })
+    }
+
+    fn save_queues<'a>(&'a self, entries: &'a [SavedEntry]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            query("DELETE FROM saved_queues")
+                .execute(&mut *tx)
+                .await?;
+
+            for (position, entry) in entries.iter().enumerate() {
+                query("INSERT INTO saved_queues (position, board, role, user_id, world_level, targets, waited) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")
+                    .bind(position as i64)
+                    .bind(encode(entry.board))
+                    .bind(entry.role.value())
+                    .bind(encode(entry.user))
+                    .bind(entry.world_level)
+                    .bind(entry.targets.join(","))
+                    .bind(entry.waited)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok(())
+        })
+    }
+
+    fn take_queues(&self) -> StorageFuture<'_, Vec<SavedEntry>> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            let rows = query_as::<_, (i64, String, i64, Option<i32>, String, i64)>("SELECT board, role, user_id, world_level, targets, waited FROM saved_queues ORDER BY position")
+                .fetch_all(&mut *tx)
+                .await?;
+
+            query("DELETE FROM saved_queues")
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+
+            Ok(rows.into_iter()
+                .filter_map(|(board, role, user, world_level, targets, waited)| Some(SavedEntry {
+                    board: decode(board),
+                    role: Role::from_value(&role)?,
+                    user: decode(user),
+                    world_level,
+                    targets: targets.split(',').filter(|i| !i.is_empty()).map(|i| i.to_owned()).collect(),
+                    waited,
+                }))
+                .collect())
+        })
+    }
+
+    fn save_gateway_sessions<'a>(&'a self, sessions: &'a [GatewaySession]) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            query("DELETE FROM gateway_sessions")
+                .execute(&mut *tx)
+                .await?;
+
+            for session in sessions {
+                query("INSERT INTO gateway_sessions (shard, total, session_id, sequence) VALUES (?1, ?2, ?3, ?4)")
+                    .bind(session.shard as i64)
+                    .bind(session.total as i64)
+                    .bind(&session.session_id)
+                    .bind(session.sequence as i64)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok(())
+        })
+    }
+
+    fn take_gateway_sessions(&self) -> StorageFuture<'_, Vec<GatewaySession>> {
+        Box::pin(async move {
+            let mut tx = self.db.begin().await?;
+
+            let rows = query_as::<_, (i64, i64, String, i64)>("SELECT shard, total, session_id, sequence FROM gateway_sessions ORDER BY shard")
+                .fetch_all(&mut *tx)
+                .await?;
+
+            query("DELETE FROM gateway_sessions")
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+
+            Ok(rows.into_iter()
+                .map(|(shard, total, session_id, sequence)| GatewaySession {
+                    shard: shard as u64,
+                    total: total as u64,
+                    session_id,
+                    sequence: sequence as u64,
+                })
+                .collect())
+        })
+    }
+
+    fn schedule_job<'a>(&'a self, name: &'a str, kind: &'a str, payload: &'a str, run_at: i64, interval: Option<i64>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            query("INSERT INTO jobs (name, kind, payload, run_at, interval_secs) VALUES (?1, ?2, ?3, ?4, ?5) ON CONFLICT (name) DO UPDATE SET kind = ?2, payload = ?3, run_at = CASE WHEN ?5 IS NULL THEN ?4 ELSE run_at END, interval_secs = ?5")
+                .bind(name)
+                .bind(kind)
+                .bind(payload)
+                .bind(run_at)
+                .bind(interval)
+                .execute(&self.db)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    fn claim_jobs<'a>(&'a self, owner: &'a str, now: i64, lease: i64) -> StorageFuture<'a, Vec<Job>> {
+        Box::pin(async move {
+            let mut jobs = query_as::<_, JobRow>("UPDATE jobs SET lease_owner = ?1, lease_until = ?2 + ?3 WHERE run_at <= ?2 AND (lease_until IS NULL OR lease_until <= ?2) RETURNING id, name, kind, payload, run_at, interval_secs, lease_until, last_error")
+                .bind(owner)
+                .bind(now)
+                .bind(lease)
+                .fetch_all(&self.db)
+                .await?
+                .into_iter()
+                .map(job)
+                .collect::<Vec<Job>>();
+
+            jobs.sort_by_key(|i| i.run_at);
+
+            Ok(jobs)
+        })
+    }
+
+    fn finish_job<'a>(&'a self, id: i64, owner: &'a str, next_run: Option<i64>, error: Option<&'a str>) -> StorageFuture<'a, ()> {
+        Box::pin(async move {
+            match next_run {
+                Some(next_run) => query("UPDATE jobs SET run_at = ?3, lease_owner = NULL, lease_until = NULL, last_error = ?4 WHERE id = ?1 AND lease_owner = ?2")
+                    .bind(id)
+                    .bind(owner)
+                    .bind(next_run)
+                    .bind(error),
+                None => query("DELETE FROM jobs WHERE id = ?1 AND lease_owner = ?2")
+                    .bind(id)
+                    .bind(owner),
+            }
+            .execute(&self.db)
+            .await?;
+
+            Ok(())
+        })
+    }
+
+    fn get_jobs(&self) -> StorageFuture<'_, Vec<Job>> {
+        Box::pin(async move {
+            Ok(query_as::<_, JobRow>("SELECT id, name, kind, payload, run_at, interval_secs, lease_until, last_error FROM jobs ORDER BY run_at")
+                .fetch_all(&self.db)
+                .await?
+                .into_iter()
+                .map(job)
+                .collect())
+        })
+    }
+
+    fn cancel_job(&self, id: i64) -> StorageFuture<'_, bool> {
+        Box::pin(async move {
+            Ok(query("DELETE FROM jobs WHERE id = ?1")
+                .bind(id)
+                .execute(&self.db)
+                .await?
+                .rows_affected() > 0)
+        })
+    }
+
+    fn close(&self) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            self.db.close().await;
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,214 @@
+//! A local stand-in for Discord's REST API. Every request is recorded and answered with a
+//! minimal model, so handlers can run their full flow without a network.
+
+// Each test file uses its own part of these helpers.
+#![allow(dead_code)]
+
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Response, Server, StatusCode,
+};
+use serde_json::{json, Value};
+
+use ww_bot::{discord_client, storage::Storage, Bot};
+
+/// The user id the fake API reports for the bot itself.
+pub const BOT_ID: u64 = 1001;
+
+const TIMESTAMP: &str = "2024-06-01T00:00:00.000000+00:00";
+
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: Method,
+    /// The route below `/api/v10`, without the query string.
+    pub path: String,
+    pub body: Value,
+    pub response: Value,
+}
+
+struct State {
+    requests: Vec<Request>,
+    next_id: u64,
+    /// Routes answered with a server error instead of their canned model.
+    failing: Vec<(Method, String)>,
+}
+
+pub struct MockDiscord {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockDiscord {
+    /// Starts the server on a free local port.
+    pub fn start() -> Self {
+        let state = Arc::new(Mutex::new(State {
+            requests: Vec::new(),
+            next_id: 5000,
+            failing: Vec::new(),
+        }));
+
+        let shared = Arc::clone(&state);
+        let service = make_service_fn(move |_| {
+            let state = Arc::clone(&shared);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| handle(Arc::clone(&state), request)))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        Self { addr, state }
+    }
+
+    /// The base url to hand to `discord_client`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// A bot whose requests all go to this server.
+    pub fn bot(&self, storage: Box<dyn Storage>) -> Bot {
+        Bot::new(discord_client("Bot test".to_owned(), Some(&self.url())), storage)
+    }
+
+    /// Makes every later request to `path` with `method` fail, as Discord does during an outage.
+    pub fn fail(&self, method: Method, path: &str) {
+        self.state.lock().expect("mock state is not poisoned").failing.push((method, path.to_owned()));
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().expect("mock state is not poisoned").requests.clone()
+    }
+
+    /// Every request made to `path` with `method`, oldest first.
+    pub fn find(&self, method: Method, path: &str) -> Vec<Request> {
+        self.requests().into_iter()
+            .filter(|i| i.method == method && i.path == path)
+            .collect()
+    }
+
+    /// The most recent request made to `path` with `method`.
+    pub fn last(&self, method: Method, path: &str) -> Request {
+        self.find(method.clone(), path).pop()
+            .unwrap_or_else(|| panic!("no {} {} was made", method, path))
+    }
+}
+
+async fn handle(state: Arc<Mutex<State>>, request: hyper::Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().trim_start_matches("/api/v10").to_owned();
+
+    let bytes = hyper::body::to_bytes(request.into_body()).await.unwrap_or_default();
+    let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+    let mut state = state.lock().expect("mock state is not poisoned");
+    let id = state.next_id;
+    state.next_id += 1;
+
+    let (status, response) = match state.failing.iter().any(|(m, p)| *m == method && *p == path) {
+        true => (StatusCode::INTERNAL_SERVER_ERROR, json!({ "code": 0, "message": "500: Internal Server Error" })),
+        false => respond(&method, &path, &body, id),
+    };
+
+    state.requests.push(Request {
+        method,
+        path,
+        body,
+        response: response.clone(),
+    });
+
+    let response = match status {
+        StatusCode::NO_CONTENT => Response::builder().status(status).body(Body::empty()),
+        _ => Response::builder()
+            .status(status)
+            .header("content-type", "application/json")
+            .body(Body::from(response.to_string())),
+    };
+
+    Ok(response.expect("mock response is valid"))
+}
+
+/// The canned answer for a route. `id` is fresh for every request and used for anything created.
+fn respond(method: &Method, path: &str, body: &Value, id: u64) -> (StatusCode, Value) {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<&str>>();
+
+    match (method, segments.as_slice()) {
+        (&Method::POST, ["interactions", _, _, "callback"]) => (StatusCode::NO_CONTENT, Value::Null),
+        (&Method::POST, ["webhooks", _, _]) => (StatusCode::OK, message(id, 1, body)),
+        (&Method::PATCH, ["webhooks", _, _, "messages", message_id]) => (StatusCode::OK, message(message_id, 1, body)),
+        (&Method::POST, ["channels", channel_id, "messages"]) => (StatusCode::OK, message(id, channel_id, body)),
+        (&Method::PATCH, ["channels", channel_id, "messages", message_id]) => (StatusCode::OK, message(message_id, channel_id, body)),
+        (&Method::POST, ["channels", channel_id, "threads"]) => (StatusCode::CREATED, json!({
+            "id": id.to_string(),
+            "type": body["type"],
+            "parent_id": channel_id,
+            "name": body["name"],
+        })),
+        (&Method::PATCH | &Method::DELETE, ["channels", channel_id]) => (StatusCode::OK, json!({
+            "id": channel_id,
+            "type": 12,
+        })),
+        (&Method::POST, ["users", "@me", "channels"]) => (StatusCode::OK, json!({
+            "id": id.to_string(),
+            "type": 1,
+            "recipients": [user(body["recipient_id"].as_str().unwrap_or("1"))],
+        })),
+        (&Method::GET, ["users", user_id]) => (StatusCode::OK, user(user_id)),
+        _ => (StatusCode::NOT_FOUND, json!({ "code": 0, "message": "404: Not Found" })),
+    }
+}
+
+/// A bot for tests that never reach Discord. Its client points at a closed port, so a stray
+/// request fails straight away instead of leaving the machine.
+pub fn offline_bot(storage: Box<dyn Storage>) -> Bot {
+    Bot::new(discord_client("Bot test".to_owned(), Some("http://127.0.0.1:1")), storage)
+}
+
+pub fn user(id: impl Display) -> Value {
+    json!({
+        "id": id.to_string(),
+        "username": format!("user{}", id),
+        "discriminator": "0",
+        "avatar": null,
+    })
+}
+
+/// A message as Discord returns it. Only the content of `body` is echoed back.
+pub fn message(id: impl Display, channel_id: impl Display, body: &Value) -> Value {
+    json!({
+        "id": id.to_string(),
+        "channel_id": channel_id.to_string(),
+        "author": user(BOT_ID),
+        "content": body["content"].as_str().unwrap_or_default(),
+        "timestamp": TIMESTAMP,
+        "edited_timestamp": null,
+        "tts": false,
+        "mention_everyone": false,
+        "mentions": [],
+        "mention_roles": [],
+        "attachments": [],
+        "embeds": [],
+        "components": [],
+        "pinned": false,
+        "type": 0,
+    })
+}
+
+/// A guild member as sent with an interaction. `permissions` is the bitset Discord computed for them.
+pub fn member(id: u64, permissions: &str) -> Value {
+    json!({
+        "user": user(id),
+        "roles": [],
+        "joined_at": TIMESTAMP,
+        "deaf": false,
+        "mute": false,
+        "flags": 0,
+        "permissions": permissions,
+    })
+}
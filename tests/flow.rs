@@ -0,0 +1,279 @@
+mod discord;
+
+use hyper::Method;
+use serde_json::{json, Value};
+use twilight_gateway::Event;
+use twilight_model::{
+    application::interaction::Interaction,
+    channel::Channel,
+    gateway::payload::incoming::{InteractionCreate, ThreadUpdate},
+    id::Id,
+};
+
+use ww_bot::{matchmaking::Role, storage::MemoryStorage, Bot};
+
+use discord::MockDiscord;
+
+const GUILD: u64 = 10;
+const BOARD_CHANNEL: u64 = 100;
+const LOG: u64 = 900;
+const ADMIN: u64 = 7;
+const USERS: [u64; 3] = [1, 2, 3];
+
+/// Builds interactions the way Discord sends them, with a fresh id for each.
+struct Interactions {
+    next_id: u64,
+}
+
+impl Interactions {
+    fn create(&mut self, kind: u8, mut fields: Value) -> Event {
+        self.next_id += 1;
+
+        fields["id"] = json!(self.next_id.to_string());
+        fields["application_id"] = json!(discord::BOT_ID.to_string());
+        fields["type"] = json!(kind);
+        fields["token"] = json!(format!("token{}", self.next_id));
+        fields["version"] = json!(1);
+
+        let interaction: Interaction = serde_json::from_value(fields).expect("interaction is valid");
+
+        Event::InteractionCreate(Box::new(InteractionCreate(interaction)))
+    }
+
+    fn command(&mut self, user: u64, permissions: &str, channel: Value, data: Value) -> Event {
+        self.create(2, json!({
+            "guild_id": GUILD.to_string(),
+            "channel": channel,
+            "member": discord::member(user, permissions),
+            "data": data,
+        }))
+    }
+
+    fn button(&mut self, user: u64, channel: Value, message: u64, custom_id: &str) -> Event {
+        self.create(3, json!({
+            "guild_id": GUILD.to_string(),
+            "channel": channel,
+            "member": discord::member(user, "0"),
+            "message": discord::message(message, channel["id"].as_str().unwrap_or_default(), &Value::Null),
+            "data": { "custom_id": custom_id, "component_type": 2, "values": [] },
+        }))
+    }
+}
+
+fn board_channel() -> Value {
+    json!({ "id": BOARD_CHANNEL.to_string(), "type": 0, "guild_id": GUILD.to_string() })
+}
+
+fn thread(id: &str, name: &str) -> Value {
+    json!({
+        "id": id,
+        "type": 12,
+        "guild_id": GUILD.to_string(),
+        "parent_id": BOARD_CHANNEL.to_string(),
+        "name": name,
+    })
+}
+
+/// The custom id of the first component in the first row of a message or response body.
+fn first_custom_id(components: &Value) -> String {
+    components[0]["components"][0]["custom_id"].as_str()
+        .expect("component has a custom id")
+        .to_owned()
+}
+
+/// Failed interactions are answered with an embed carrying a reference footer.
+fn assert_no_failures(discord: &MockDiscord) {
+    for request in discord.requests() {
+        let embeds = request.body["embeds"].as_array()
+            .or_else(|| request.body["data"]["embeds"].as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        for embed in embeds {
+            let footer = embed["footer"]["text"].as_str().unwrap_or_default();
+            assert!(!footer.starts_with("Reference:"), "{} {} failed: {}", request.method, request.path, embed);
+        }
+    }
+}
+
+/// Has an administrator post an elite board, returning its message id and co-op button.
+async fn post_board(bot: &Bot, discord: &MockDiscord, interactions: &mut Interactions) -> (u64, String) {
+    bot.process(interactions.command(ADMIN, "8", board_channel(), json!({
+        "id": "3000",
+        "name": "setup",
+        "type": 1,
+        "options": [{ "name": "kind", "type": 3, "value": "elite" }],
+    }))).await;
+
+    let board = discord.last(Method::POST, &format!("/channels/{}/messages", BOARD_CHANNEL));
+    let board_id = board.response["id"].as_str().expect("board has an id").parse::<u64>().expect("id is numeric");
+    let buttons = discord.last(Method::PATCH, &format!("/channels/{}/messages/{}", BOARD_CHANNEL, board_id));
+    assert_eq!(buttons.body["components"][0]["components"].as_array().map(Vec::len), Some(3));
+
+    (board_id, first_custom_id(&buttons.body["components"]))
+}
+
+#[tokio::test]
+async fn queue_session_end_and_report() {
+    std::env::set_var("LOG", LOG.to_string());
+
+    let discord = MockDiscord::start();
+    let bot = discord.bot(Box::new(MemoryStorage::new()));
+    bot.storage.setup().await.expect("storage is set up");
+
+    let mut interactions = Interactions { next_id: 2000 };
+    let (board_id, coop) = post_board(&bot, &discord, &mut interactions).await;
+
+    // Three users join the co-op queue, and the third click forms a group.
+    for user in USERS {
+        bot.process(interactions.button(user, board_channel(), board_id, &coop)).await;
+    }
+
+    let created = discord.last(Method::POST, &format!("/channels/{}/threads", BOARD_CHANNEL));
+    let thread_id = created.response["id"].as_str().expect("thread has an id").to_owned();
+    let thread_name = created.body["name"].as_str().expect("thread has a name").to_owned();
+    assert_eq!(created.body["type"], json!(12));
+
+    let welcome = discord.last(Method::POST, &format!("/channels/{}/messages", thread_id));
+    assert_eq!(welcome.body["content"], json!("<@1> <@2> <@3>"));
+
+    let thread_channel = Id::new(thread_id.parse::<u64>().expect("id is numeric"));
+    assert!(bot.storage.is_thread(thread_channel).await.expect("storage works"));
+
+    // The first member asks to end the session and the second confirms.
+    bot.process(interactions.command(USERS[0], "0", thread(&thread_id, &thread_name), json!({
+        "id": "3001",
+        "name": "end",
+        "type": 1,
+    }))).await;
+
+    let request = discord.requests().into_iter()
+        .rev()
+        .find(|i| i.path.ends_with("/callback"))
+        .expect("/end was answered");
+    let confirm = first_custom_id(&request.body["data"]["components"]);
+
+    bot.process(interactions.button(USERS[1], thread(&thread_id, &thread_name), 6000, &confirm)).await;
+
+    let archive = discord.last(Method::PATCH, &format!("/channels/{}", thread_id));
+    assert_eq!((&archive.body["archived"], &archive.body["locked"]), (&json!(true), &json!(true)));
+
+    // Discord then reports the thread as archived, which awards rep and sends the polls.
+    let mut archived = thread(&thread_id, &thread_name);
+    archived["thread_metadata"] = json!({
+        "archived": true,
+        "auto_archive_duration": 1440,
+        "archive_timestamp": "2024-06-01T01:00:00.000000+00:00",
+        "locked": true,
+    });
+    let archived: Channel = serde_json::from_value(archived).expect("thread is valid");
+    bot.process(Event::ThreadUpdate(Box::new(ThreadUpdate(archived)))).await;
+
+    for user in USERS {
+        assert_eq!(bot.storage.get_rep(Id::new(user)).await.expect("storage works"), Some(1));
+    }
+    assert!(!bot.storage.is_thread(thread_channel).await.expect("storage works"));
+
+    let dms = discord.find(Method::POST, "/users/@me/channels");
+    assert_eq!(dms.len(), 3);
+
+    // The first member reports the second from their poll.
+    let dm = dms.iter()
+        .find(|i| i.body["recipient_id"] == json!(USERS[0].to_string()))
+        .expect("first member got a poll");
+    let dm_channel = dm.response["id"].as_str().expect("dm has an id").to_owned();
+    let poll = discord.last(Method::POST, &format!("/channels/{}/messages", dm_channel));
+    let poll_id = poll.response["id"].as_str().expect("poll has an id").to_owned();
+    let select = first_custom_id(&poll.body["components"]);
+    let dm_channel_json = json!({ "id": dm_channel, "type": 1 });
+
+    bot.process(interactions.create(3, json!({
+        "channel": dm_channel_json,
+        "user": discord::user(USERS[0]),
+        "message": discord::message(&poll_id, &dm_channel, &Value::Null),
+        "data": { "custom_id": select, "component_type": 3, "values": [USERS[1].to_string()] },
+    }))).await;
+
+    let request = discord.requests().into_iter()
+        .rev()
+        .find(|i| i.path.ends_with("/callback"))
+        .expect("the poll was answered");
+    assert_eq!(request.body["type"], json!(9));
+    let modal = request.body["data"]["custom_id"].as_str().expect("modal has a custom id").to_owned();
+    let reason = first_custom_id(&request.body["data"]["components"]);
+
+    bot.process(interactions.create(5, json!({
+        "channel": dm_channel_json,
+        "user": discord::user(USERS[0]),
+        "message": discord::message(&poll_id, &dm_channel, &Value::Null),
+        "data": {
+            "custom_id": modal,
+            "components": [{ "type": 1, "components": [{ "type": 4, "custom_id": reason, "value": "Left without a word" }] }],
+        },
+    }))).await;
+
+    let report = discord.last(Method::POST, &format!("/channels/{}/messages", LOG));
+    let description = report.body["embeds"][0]["description"].as_str().expect("report has a description");
+    assert!(description.contains("<@1> against <@2>"), "{}", description);
+    assert!(description.contains("Left without a word"), "{}", description);
+
+    assert_eq!(bot.storage.get_rep(Id::new(USERS[1])).await.expect("storage works"), Some(0));
+
+    // The poll keeps only the member who has not been reported yet.
+    let updated = discord.last(Method::PATCH, &format!("/channels/{}/messages/{}", dm_channel, poll_id));
+    let options = updated.body["components"][0]["components"][0]["options"].as_array().expect("poll still has a menu");
+    assert_eq!(options.len(), 1);
+    assert_eq!(options[0]["value"], json!(USERS[2].to_string()));
+
+    assert_no_failures(&discord);
+}
+
+#[tokio::test]
+async fn clicks_on_unknown_boards_are_refused() {
+    let discord = MockDiscord::start();
+    let bot = discord.bot(Box::new(MemoryStorage::new()));
+    bot.storage.setup().await.expect("storage is set up");
+
+    let mut interactions = Interactions { next_id: 2000 };
+    let (board_id, coop) = post_board(&bot, &discord, &mut interactions).await;
+
+    // The same button on a message the bot has no record of, like a board from an older version.
+    let unknown = board_id + 1;
+    bot.process(interactions.button(USERS[0], board_channel(), unknown, &coop)).await;
+
+    // The click was deferred, so the refusal is either a response or a followup.
+    let reply = discord.requests().into_iter()
+        .rev()
+        .find(|i| i.path.ends_with("/callback") || i.path.starts_with("/webhooks/"))
+        .expect("the click was answered");
+    let embed = reply.body["data"]["embeds"].get(0).unwrap_or(&reply.body["embeds"][0]).clone();
+    let description = embed["description"].as_str().expect("reply has a description");
+    assert!(description.contains("/setup"), "{}", description);
+
+    assert!(bot.board(Id::new(unknown)).await.is_none());
+    assert!(!bot.storage.in_thread(Id::new(USERS[0])).await.expect("storage works"));
+}
+
+#[tokio::test]
+async fn groups_without_a_thread_are_requeued() {
+    let discord = MockDiscord::start();
+    let bot = discord.bot(Box::new(MemoryStorage::new()));
+    bot.storage.setup().await.expect("storage is set up");
+
+    let mut interactions = Interactions { next_id: 2000 };
+    let (board_id, coop) = post_board(&bot, &discord, &mut interactions).await;
+
+    discord.fail(Method::POST, &format!("/channels/{}/threads", BOARD_CHANNEL));
+    for user in USERS {
+        bot.process(interactions.button(user, board_channel(), board_id, &coop)).await;
+    }
+
+    // The group is back at the head of the co-op queue in the order it joined.
+    let queue = bot.board(Id::new(board_id)).await.expect("board is known");
+    let waiting = queue.lock().await.queues().queue(Role::Coop).iter().map(|i| i.user.get()).collect::<Vec<u64>>();
+    assert_eq!(waiting, USERS);
+
+    for user in USERS {
+        assert!(!bot.storage.in_thread(Id::new(user)).await.expect("storage works"));
+    }
+}
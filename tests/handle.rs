@@ -0,0 +1,178 @@
+mod discord;
+
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use twilight_gateway::Event;
+use twilight_model::{
+    application::interaction::{application_command::CommandData, Interaction, InteractionData},
+    channel::Channel,
+    gateway::payload::incoming::InteractionCreate,
+};
+
+use ww_bot::error::BotError;
+use ww_bot::registry::ComponentData;
+use ww_bot::storage::MemoryStorage;
+use ww_bot::interactions::{end::End, queue::Queue, rep};
+use ww_bot::matchmaking::Role;
+
+use discord::offline_bot;
+
+/// An interaction with nothing but `data` and the fields Discord always sends.
+fn interaction(kind: u8, data: Value) -> Interaction {
+    let interaction = json!({
+        "id": "1000",
+        "application_id": "1001",
+        "type": kind,
+        "token": "token",
+        "version": 1,
+        "data": data,
+    });
+
+    serde_json::from_value(interaction).expect("interaction is valid")
+}
+
+fn command(name: &str) -> Interaction {
+    interaction(2, json!({ "id": "1002", "name": name, "type": 1 }))
+}
+
+fn button(custom_id: &str) -> Interaction {
+    interaction(3, json!({ "custom_id": custom_id, "component_type": 2, "values": [] }))
+}
+
+fn modal(custom_id: &str, components: Value) -> Interaction {
+    interaction(5, json!({ "custom_id": custom_id, "components": components }))
+}
+
+/// Runs the event the way the gateway loop does and fails if the task panicked.
+async fn process(event: Event) {
+    let bot = Arc::new(offline_bot(Box::new(MemoryStorage::new())));
+    let task = tokio::spawn(async move { bot.process(event).await });
+
+    assert!(task.await.is_ok(), "handler panicked");
+}
+
+async fn process_interaction(interaction: Interaction) {
+    process(Event::InteractionCreate(Box::new(InteractionCreate(interaction)))).await;
+}
+
+#[tokio::test]
+async fn interactions_without_data() {
+    // Discord's models require data for these kinds, so it is taken out after parsing.
+    for mut interaction in [command("ping"), button("v1:queue:coop"), modal("v1:reportreason", json!([]))] {
+        interaction.data = None;
+        process_interaction(interaction).await;
+    }
+}
+
+#[tokio::test]
+async fn unknown_command() {
+    process_interaction(command("removed")).await;
+}
+
+#[tokio::test]
+async fn commands_outside_a_channel() {
+    for name in ["end", "leave", "setup", "queue", "rep", "profile", "config", "jobs", "ping"] {
+        process_interaction(command(name)).await;
+    }
+}
+
+#[tokio::test]
+async fn malformed_custom_ids() {
+    for custom_id in ["", "garbage", "v1", "v1:queue", "v1:queue:healer", "v1:cancel:0", "v1:end:", "v1:draft:a:b", "report:1:2", "QueueA-"] {
+        process_interaction(button(custom_id)).await;
+    }
+}
+
+#[tokio::test]
+async fn malformed_modals() {
+    process_interaction(modal("v1:draftmodal:token", json!([]))).await;
+    process_interaction(modal("v1:draftmodal:token", json!([{ "type": 1, "components": [] }]))).await;
+    process_interaction(modal("v1:reportreason", json!([]))).await;
+}
+
+#[tokio::test]
+async fn queue_button_without_message() {
+    let bot = offline_bot(Box::new(MemoryStorage::new()));
+    let result = Queue::handle_queue_generic(button("v1:queue:coop"), &bot, Role::Coop).await;
+
+    assert!(matches!(result, Err(BotError::Missing(_))), "{:?}", result);
+}
+
+fn command_data(interaction: &mut Interaction) -> CommandData {
+    let Some(InteractionData::ApplicationCommand(data)) = interaction.data.take() else {
+        panic!("command has data");
+    };
+
+    *data
+}
+
+#[tokio::test]
+async fn unknown_command_is_refused() {
+    let bot = offline_bot(Box::new(MemoryStorage::new()));
+    let mut interaction = command("removed");
+    let data = command_data(&mut interaction);
+
+    let result = bot.dispatch_command(interaction, data).await;
+
+    assert!(matches!(result, Err(BotError::User(_))), "{:?}", result);
+}
+
+#[tokio::test]
+async fn moderator_commands_are_denied() {
+    for name in ["setup", "config", "rep"] {
+        let bot = offline_bot(Box::new(MemoryStorage::new()));
+        let mut interaction = command(name);
+        let data = command_data(&mut interaction);
+
+        let result = bot.dispatch_command(interaction, data).await;
+
+        assert!(matches!(result, Err(BotError::PermissionDenied)), "{}: {:?}", name, result);
+    }
+}
+
+#[tokio::test]
+async fn unhandled_component_is_refused() {
+    let bot = offline_bot(Box::new(MemoryStorage::new()));
+    let mut interaction = button("v1:reportreason");
+    let Some(InteractionData::MessageComponent(data)) = interaction.data.take() else {
+        panic!("button has data");
+    };
+
+    let result = bot.dispatch_component(interaction, ComponentData::Message(data)).await;
+
+    assert!(matches!(result, Err(BotError::CustomId(_))), "{:?}", result);
+}
+
+#[tokio::test]
+async fn end_without_channel() {
+    let bot = offline_bot(Box::new(MemoryStorage::new()));
+    let mut interaction = command("end");
+    let data = command_data(&mut interaction);
+
+    let result = End::handle(interaction, data, &bot).await;
+
+    assert!(matches!(result, Err(BotError::User(_))), "{:?}", result);
+}
+
+#[tokio::test]
+async fn report_without_reason() {
+    let bot = offline_bot(Box::new(MemoryStorage::new()));
+    let mut interaction = modal("v1:draftmodal:token", json!([]));
+    let Some(InteractionData::ModalSubmit(data)) = interaction.data.take() else {
+        panic!("modal has data");
+    };
+
+    let result = rep::handle_text(interaction, data, &bot, "token").await;
+
+    assert!(matches!(result, Err(BotError::Missing(_))), "{:?}", result);
+}
+
+#[tokio::test]
+async fn thread_update_without_metadata() {
+    let bot = offline_bot(Box::new(MemoryStorage::new()));
+    let channel: Channel = serde_json::from_value(json!({ "id": "1003", "type": 12 }))
+        .expect("channel is valid");
+
+    assert!(bot.thread_update(channel).await.is_ok());
+}
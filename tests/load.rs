@@ -0,0 +1,66 @@
+mod discord;
+
+use std::time::{Duration, Instant};
+
+use futures_util::future::join_all;
+use twilight_model::id::Id;
+
+use ww_bot::{interactions::queue::Queue, matchmaking::Role, storage::MemoryStorage, Bot};
+
+/// Roughly a round trip to a database in another region.
+const LATENCY: Duration = Duration::from_millis(50);
+
+/// Joining never reaches Discord, only storage, which answers after `LATENCY`.
+fn slow_bot() -> Bot {
+    discord::offline_bot(Box::new(MemoryStorage::with_latency(LATENCY)))
+}
+
+#[tokio::test]
+async fn clicks_on_separate_boards_do_not_wait_for_each_other() {
+    const BOARDS: u64 = 40;
+
+    let bot = slow_bot();
+
+    let started = Instant::now();
+    let results = join_all((1..=BOARDS).map(|i| Queue::join(&bot, Id::new(i), Id::new(i), Role::Coop))).await;
+    let elapsed = started.elapsed();
+
+    for result in results {
+        let (_, _, groups) = result.expect("join succeeds");
+        assert!(groups.is_empty());
+    }
+
+    // Each click makes three round trips. Had any of them run under a shared lock, the clicks
+    // would have queued up behind each other and taken at least `BOARDS * LATENCY`.
+    assert!(elapsed < LATENCY * 10, "{} clicks took {:?}", BOARDS, elapsed);
+
+    for board in 1..=BOARDS {
+        let queue = bot.board(Id::new(board)).await.expect("board was created");
+        assert!(queue.lock().await.queues().contains(&Id::new(board)));
+    }
+}
+
+#[tokio::test]
+async fn concurrent_clicks_on_one_board_form_whole_groups() {
+    const USERS: u64 = 30;
+
+    let bot = slow_bot();
+    let board = Id::new(1);
+
+    let results = join_all((1..=USERS).map(|i| Queue::join(&bot, board, Id::new(i), Role::Coop))).await;
+
+    let mut grouped = Vec::new();
+    for result in results {
+        let (_, _, groups) = result.expect("join succeeds");
+        for group in groups {
+            assert_eq!(group.members.len(), 3);
+            grouped.extend(group.members.into_iter().map(|i| i.user.get()));
+        }
+    }
+
+    grouped.sort_unstable();
+    assert_eq!(grouped, (1..=USERS).collect::<Vec<u64>>());
+
+    let queue = bot.board(board).await.expect("board was created");
+    assert!(queue.lock().await.queues().is_empty());
+}
@@ -0,0 +1,67 @@
+mod discord;
+
+use std::time::Duration;
+
+use hyper::Method;
+use twilight_model::id::Id;
+
+use ww_bot::{
+    reconcile::{Action, Rules},
+    storage::MemoryStorage,
+    Bot,
+};
+
+use discord::MockDiscord;
+
+const LOG: u64 = 900;
+/// The mock answers every channel lookup with a 404, so this thread reads as deleted.
+const THREAD: u64 = 700;
+
+fn rules(deleted: Action) -> Rules {
+    Rules {
+        deleted,
+        archived: Action::Finalize,
+        stale: Action::Keep,
+        stale_after: Duration::from_secs(24 * 60 * 60),
+        interval: None,
+    }
+}
+
+async fn bot_with_thread(discord: &MockDiscord) -> Bot {
+    std::env::set_var("LOG", LOG.to_string());
+
+    let bot = discord.bot(Box::new(MemoryStorage::new()));
+    bot.storage.insert_thread(Id::new(THREAD), Id::new(20), &[Id::new(1), Id::new(2)]).await.expect("storage works");
+
+    bot
+}
+
+#[tokio::test]
+async fn deleted_threads_stop_blocking_their_members() {
+    let discord = MockDiscord::start();
+    let bot = bot_with_thread(&discord).await;
+
+    bot.reconcile(&rules(Action::Delete)).await.expect("reconcile succeeds");
+
+    assert!(!bot.storage.in_thread(Id::new(1)).await.expect("storage works"));
+    assert!(bot.storage.get_threads().await.expect("storage works").is_empty());
+
+    let report = discord.last(Method::POST, &format!("/channels/{}/messages", LOG));
+    let description = report.body["embeds"][0]["description"].as_str().expect("report has a description");
+    assert!(description.contains(&format!("<#{}> was deleted, so it was deleted.", THREAD)), "{}", description);
+
+    // Nothing is left to do, so a second pass reports nothing.
+    bot.reconcile(&rules(Action::Delete)).await.expect("reconcile succeeds");
+    assert_eq!(discord.find(Method::POST, &format!("/channels/{}/messages", LOG)).len(), 1);
+}
+
+#[tokio::test]
+async fn kept_threads_are_left_alone() {
+    let discord = MockDiscord::start();
+    let bot = bot_with_thread(&discord).await;
+
+    bot.reconcile(&rules(Action::Keep)).await.expect("reconcile succeeds");
+
+    assert!(bot.storage.in_thread(Id::new(1)).await.expect("storage works"));
+    assert!(discord.find(Method::POST, &format!("/channels/{}/messages", LOG)).is_empty());
+}
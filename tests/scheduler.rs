@@ -0,0 +1,50 @@
+mod discord;
+
+use std::time::{Duration, SystemTime};
+
+use ww_bot::{
+    reconcile::Reconcile,
+    scheduler::{unix_now, JobHandler},
+    storage::MemoryStorage,
+};
+
+use discord::offline_bot;
+
+#[tokio::test]
+async fn one_shot_jobs_run_once() {
+    let bot = offline_bot(Box::new(MemoryStorage::new()));
+    bot.schedule_once("catch up", Reconcile::KIND, "", SystemTime::now()).await.expect("storage works");
+
+    assert_eq!(bot.run_due_jobs().await.expect("jobs run"), 1);
+    assert!(bot.storage.get_jobs().await.expect("storage works").is_empty());
+    assert_eq!(bot.run_due_jobs().await.expect("jobs run"), 0);
+}
+
+#[tokio::test]
+async fn recurring_jobs_wait_for_their_interval() {
+    let bot = offline_bot(Box::new(MemoryStorage::new()));
+    bot.schedule_every(Reconcile::KIND, Reconcile::KIND, "", Duration::from_secs(60)).await.expect("storage works");
+
+    assert_eq!(bot.run_due_jobs().await.expect("jobs run"), 0);
+
+    // Scheduling again at startup doesn't push the next run back.
+    let jobs = bot.storage.get_jobs().await.expect("storage works");
+    bot.schedule_every(Reconcile::KIND, Reconcile::KIND, "", Duration::from_secs(120)).await.expect("storage works");
+    let rescheduled = bot.storage.get_jobs().await.expect("storage works");
+    assert_eq!(rescheduled[0].run_at, jobs[0].run_at);
+    assert_eq!(rescheduled[0].interval, Some(120));
+}
+
+#[tokio::test]
+async fn failed_jobs_are_kept_with_their_error() {
+    let bot = offline_bot(Box::new(MemoryStorage::new()));
+    bot.schedule_once("mystery", "removed", "", SystemTime::now()).await.expect("storage works");
+
+    assert_eq!(bot.run_due_jobs().await.expect("jobs run"), 1);
+
+    let jobs = bot.storage.get_jobs().await.expect("storage works");
+    assert_eq!(jobs.len(), 1);
+    assert!(jobs[0].run_at > unix_now());
+    assert_eq!(jobs[0].lease_until, None);
+    assert_eq!(jobs[0].last_error.as_deref(), Some("no handler for jobs of kind removed"));
+}